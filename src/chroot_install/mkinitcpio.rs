@@ -0,0 +1,241 @@
+use crate::{
+    config::Config,
+    utils::{command::Command, shell_conf::ShellConf},
+};
+
+/// Relative order of the hooks the installer knows about, hooks missing from
+/// this list are left where they are.
+const HOOKS_ORDER: &[&str] = &[
+    "base",
+    "udev",
+    "systemd",
+    "autodetect",
+    "microcode",
+    "modconf",
+    "kms",
+    "keyboard",
+    "keymap",
+    "sd-vconsole",
+    "consolefont",
+    "block",
+    "mdadm_udev",
+    "encrypt",
+    "sd-encrypt",
+    "lvm2",
+    "filesystems",
+    "resume",
+    "fsck",
+];
+
+fn hook_rank(hook: &str) -> Option<usize> {
+    HOOKS_ORDER.iter().position(|&known| known == hook)
+}
+
+#[derive(Debug)]
+pub(crate) struct Hooks(Vec<String>);
+
+impl Hooks {
+    pub(crate) fn new(hooks: Vec<String>) -> Hooks {
+        Hooks(hooks)
+    }
+
+    fn contains(&self, hook: &str) -> bool {
+        self.0.iter().any(|item| item == hook)
+    }
+
+    /// Whether the image is systemd based, which takes `rd.luks` parameters
+    /// instead of `cryptdevice`.
+    pub(crate) fn is_systemd(&self) -> bool {
+        self.contains("systemd")
+    }
+
+    /// Returns the hook providing the same feature for systemd based images.
+    fn resolve<'h>(&self, hook: &'h str) -> Option<&'h str> {
+        if !self.is_systemd() {
+            return Some(hook);
+        }
+        match hook {
+            "encrypt" => Some("sd-encrypt"),
+            "keymap" | "consolefont" => Some("sd-vconsole"),
+            // systemd resumes from hibernation by itself.
+            "resume" => None,
+            hook => Some(hook),
+        }
+    }
+
+    /// Inserts the hook right before the first hook which must follow it. An
+    /// already present hook is moved if it is out of order.
+    pub(crate) fn insert(&mut self, hook: &str) {
+        let hook = match self.resolve(hook) {
+            Some(hook) => hook,
+            None => return,
+        };
+        let rank = match hook_rank(hook) {
+            Some(rank) => rank,
+            None => {
+                if !self.contains(hook) {
+                    self.0.push(hook.to_owned());
+                }
+                return;
+            }
+        };
+        self.0.retain(|item| item != hook);
+
+        let index = self
+            .0
+            .iter()
+            .position(|item| matches!(hook_rank(item), Some(other) if other > rank))
+            .unwrap_or(self.0.len());
+
+        self.0.insert(index, hook.to_owned());
+    }
+
+    pub(crate) fn as_slice(&self) -> &[String] {
+        &self.0
+    }
+}
+
+pub(crate) struct MkinitcpioConfigurator<'a> {
+    config: &'a Config,
+    conf: ShellConf,
+}
+
+impl<'a> MkinitcpioConfigurator<'a> {
+    pub(crate) fn new(config: &'a Config) -> MkinitcpioConfigurator<'a> {
+        let conf = ShellConf::open("/etc/mkinitcpio.conf");
+        MkinitcpioConfigurator { config, conf }
+    }
+
+    fn required_hooks(&self) -> Vec<&str> {
+        let mut hooks = vec![];
+
+        if self.config.partitions().root.encryption {
+            hooks.extend(&["keyboard", "keymap", "encrypt"]);
+
+            // The password prompt uses the console font as well.
+            if !self.config.locale().font().is_empty() {
+                hooks.push("consolefont");
+            }
+        }
+        if self.config.swap().hibernate() {
+            hooks.push("resume");
+        }
+        hooks.extend(self.config.mkinitcpio().hooks().iter().map(|hook| &**hook));
+        hooks
+    }
+
+    /// HOOKS of mkinitcpio.conf with the required ones added.
+    pub(crate) fn hooks(&self) -> Hooks {
+        let mut hooks = Hooks::new(self.conf.array("HOOKS").unwrap_or_default());
+
+        for hook in self.required_hooks() {
+            hooks.insert(hook);
+        }
+        hooks
+    }
+
+    fn set_hooks(&mut self) -> &mut Self {
+        let hooks = self.hooks();
+        self.conf.set_array("HOOKS", hooks.as_slice());
+        self
+    }
+
+    fn set_arrays(&mut self) -> &mut Self {
+        let settings = self.config.mkinitcpio();
+        let arrays = [
+            ("MODULES", settings.modules()),
+            ("FILES", settings.files()),
+            ("BINARIES", settings.binaries()),
+        ];
+
+        for (key, items) in arrays.iter() {
+            if !items.is_empty() {
+                self.conf.set_array(key, items);
+            }
+        }
+        if let Some(compression) = settings.compression() {
+            self.conf.set_value("COMPRESSION", compression);
+        }
+        self
+    }
+
+    fn generate(&mut self) {
+        let status = Command::new("mkinitcpio")
+            .args(&["-p", "linux"])
+            .spawn()
+            .unwrap()
+            .wait()
+            .unwrap();
+
+        assert!(status.success());
+    }
+
+    pub(crate) fn run(&mut self) {
+        self.set_hooks().set_arrays().conf.save();
+        self.generate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUSYBOX: &str =
+        "base udev autodetect microcode modconf kms keyboard keymap consolefont block filesystems fsck";
+    const SYSTEMD: &str =
+        "base systemd autodetect microcode modconf kms keyboard sd-vconsole block filesystems fsck";
+
+    fn hooks(hooks: &str) -> Hooks {
+        Hooks::new(hooks.split(' ').map(|hook| hook.to_owned()).collect())
+    }
+
+    fn inserted(default: &str, required: &[&str]) -> String {
+        let mut hooks = hooks(default);
+
+        for hook in required {
+            hooks.insert(hook);
+        }
+        hooks.as_slice().join(" ")
+    }
+
+    #[test]
+    fn inserts_encrypt_into_busybox_default() {
+        assert_eq!(
+            inserted(BUSYBOX, &["keyboard", "keymap", "encrypt", "resume"]),
+            "base udev autodetect microcode modconf kms keyboard keymap consolefont block \
+             encrypt filesystems resume fsck"
+        );
+        assert!(!hooks(BUSYBOX).is_systemd());
+    }
+
+    #[test]
+    fn inserts_sd_encrypt_into_systemd_default() {
+        assert_eq!(
+            inserted(SYSTEMD, &["keyboard", "keymap", "encrypt", "consolefont"]),
+            "base systemd autodetect microcode modconf kms keyboard sd-vconsole block \
+             sd-encrypt filesystems fsck"
+        );
+        assert!(hooks(SYSTEMD).is_systemd());
+    }
+
+    #[test]
+    fn moves_hook_out_of_order() {
+        assert_eq!(
+            inserted("base udev block filesystems keyboard fsck", &["keyboard"]),
+            "base udev keyboard block filesystems fsck"
+        );
+    }
+
+    #[test]
+    fn drops_resume_under_systemd() {
+        assert_eq!(inserted(SYSTEMD, &["resume"]), SYSTEMD);
+    }
+
+    #[test]
+    fn appends_unknown_hook_once() {
+        assert_eq!(
+            inserted(BUSYBOX, &["plymouth", "plymouth"]),
+            format!("{} plymouth", BUSYBOX)
+        );
+    }
+}
@@ -1,26 +1,54 @@
 use crate::{config::Config, errors::ALIResult, stage2_chroot_install};
 
+pub(crate) mod mkinitcpio;
+pub(crate) mod network;
 pub(crate) mod package_configurator;
+pub(crate) mod ssh;
+pub(crate) mod users;
 
 mod private {
     use crate::utils::partitions::Partitions;
 
     use {
-        super::package_configurator::PackageConfigurator,
+        super::{
+            mkinitcpio::MkinitcpioConfigurator, network::NetworkConfigurator,
+            package_configurator::PackageConfigurator, ssh::SshConfigurator,
+            users::UserConfigurator,
+        },
         crate::{
-            config::Config,
-            utils::{command::Command, pacman_install},
+            config::{Config, HardwareClock, SwapKind, TimeSync},
+            errors::{self, ALIResult},
+            utils::{command::Command, efibootmgr, pacman_install, shell_conf::ShellConf, Service},
         },
-        log::error,
+        snafu::{ensure, OptionExt, ResultExt},
         std::{
-            fs::{self, File},
-            io::{prelude::*, stdin},
-            os::unix::fs::symlink,
+            fs::{self, create_dir_all},
+            io::prelude::*,
+            os::unix::fs::{symlink, PermissionsExt},
             path::Path,
-            process::{ExitStatus, Stdio},
+            process::Stdio,
         },
     };
 
+    const GRUB_CUSTOM_SCRIPT: &str = "/etc/grub.d/40_custom";
+    const GRUB_PASSWORD_BEGIN: &str = "### BEGIN installer superusers ###";
+    const GRUB_PASSWORD_END: &str = "### END installer superusers ###";
+    const GRUB_LINUX_SCRIPT: &str = "/etc/grub.d/10_linux";
+    const GRUB_CLASS: &str = "CLASS=\"--class gnu-linux";
+    const GRUB_UNRESTRICTED_HOOK: &str = "/etc/pacman.d/hooks/95-grub-unrestricted.hook";
+    const GRUB_UNRESTRICTED_HOOK_CONTENTS: &str = "\
+[Trigger]
+Type = Path
+Operation = Install
+Operation = Upgrade
+Target = etc/grub.d/10_linux
+
+[Action]
+Description = Keeping the GRUB boot entries bootable without password...
+When = PostTransaction
+Exec = /usr/bin/sed -i '/^CLASS=\"--class gnu-linux/{/--unrestricted/!s/\"$/ --unrestricted\"/}' /etc/grub.d/10_linux
+";
+
     pub(crate) struct ChrootInstaller<'a> {
         config: &'a Config,
         efi: bool,
@@ -32,18 +60,24 @@ mod private {
             ChrootInstaller { config, efi }
         }
 
-        fn get_uuid(&self) -> String {
-            let mut buffer = String::new();
-
-            let partitions = Partitions::new(
+        fn partitions(&self) -> Partitions {
+            let mut partitions = Partitions::new(
                 self.config.system().drive_path(),
                 &self.config.partitions().root.device,
+                &self.config.swap().device,
                 &self.config.partitions().boot.device,
                 &self.config.partitions().efi.device,
             );
+            partitions.update();
+            partitions
+        }
+
+        fn get_uuid(&self, device: &Path) -> String {
+            let mut buffer = String::new();
+
             let mut lsblk = Command::new("lsblk")
                 .args(&["-dno", "UUID"])
-                .arg(partitions.root().unwrap())
+                .arg(device)
                 .stdout(Stdio::piped())
                 .spawn()
                 .unwrap();
@@ -60,229 +94,582 @@ mod private {
             buffer
         }
 
-        fn grub_cmdline(&self, uuid: &str) -> String {
+        /// Physical offset of the swap file, in pages, as expected by `resume_offset`.
+        ///
+        /// The validator makes sure the target has btrfs-progs or e2fsprogs.
+        fn swap_file_offset(&self) -> ALIResult<String> {
+            let file = self.config.swap().file();
+            let btrfs = self.config.partitions().root.fs == "btrfs";
+
+            let mut command = if btrfs {
+                let mut btrfs = Command::new("btrfs");
+                btrfs.args(&["inspect-internal", "map-swapfile", "-r"]);
+                btrfs
+            } else {
+                let mut filefrag = Command::new("filefrag");
+                filefrag.arg("-v");
+                filefrag
+            };
+            let output = command.arg(file).output().unwrap();
+
+            ensure!(
+                output.status.success(),
+                errors::CommandExecutionSnafu {
+                    message: command.command_line(),
+                }
+            );
+            let output = String::from_utf8_lossy(&output.stdout);
+
+            if btrfs {
+                return Ok(output.trim().to_owned());
+            }
+            // ext:     logical_offset:        physical_offset: length:   expected: flags:
+            //   0:        0..       0:      38912..     38912:      1:
+            let start = output
+                .lines()
+                .find(|line| line.trim_start().starts_with("0:"))
+                .and_then(|first_extent| first_extent.split(':').nth(2))
+                .and_then(|physical_offset| physical_offset.split_once(".."))
+                .map(|(start, _)| start.trim().to_owned());
+
+            start.context(errors::SwapFileOffsetSnafu { file })
+        }
+
+        fn resume_params(&self) -> ALIResult<Vec<String>> {
+            let root = &self.config.partitions().root;
+
+            Ok(match self.config.swap().kind() {
+                SwapKind::None => vec![],
+                SwapKind::Partition => {
+                    let uuid = self.get_uuid(self.partitions().swap().unwrap());
+                    vec![format!("resume=UUID={}", uuid)]
+                }
+                SwapKind::File => {
+                    let device = if root.encryption {
+                        root.crypt_mapping_path().to_str().unwrap().to_owned()
+                    } else {
+                        format!("UUID={}", self.get_uuid(self.partitions().root().unwrap()))
+                    };
+                    vec![
+                        format!("resume={}", device),
+                        format!("resume_offset={}", self.swap_file_offset()?),
+                    ]
+                }
+            })
+        }
+
+        fn grub_cmdline(&self) -> ALIResult<String> {
+            let mut params = vec!["loglevel=3".to_owned(), "quiet".to_owned()];
+
             if self.config.partitions().root.encryption {
                 let mapping = &self.config.partitions().root.crypt_mapping;
+                let uuid = self.get_uuid(self.partitions().root().unwrap());
+
+                // mkinitcpio.conf gets the hooks later, the same ones are
+                // resolved here.
+                if MkinitcpioConfigurator::new(self.config)
+                    .hooks()
+                    .is_systemd()
+                {
+                    params.push(format!("rd.luks.name={}={}", uuid, mapping));
+                } else {
+                    params.push(format!("cryptdevice=UUID={}:{}", uuid, mapping));
+                }
+                params.push(format!("root=/dev/mapper/{}", mapping));
+            }
+            if self.config.swap().hibernate() {
+                params.extend(self.resume_params()?);
+            }
+            if let Some(console) = self.config.system().serial_console() {
+                params.extend(console.kernel_params());
+            }
+            Ok(params.join(" "))
+        }
 
-                format!(
-                    "loglevel=3 quiet cryptdevice=UUID={}:{} root=/dev/mapper/{}",
-                    uuid, mapping, mapping
-                )
-            } else {
-                "loglevel=3 quiet".to_owned()
+        fn enable_locale(lines: &mut Vec<String>, locale: &str) -> ALIResult<()> {
+            let name = locale.split_whitespace().next().unwrap();
+            let prefix = format!("{} ", name);
+
+            if lines.iter().any(|line| line.starts_with(&prefix)) {
+                return Ok(());
+            }
+            let commented = lines.iter().position(
+                |line| matches!(line.strip_prefix('#'), Some(line) if line.starts_with(&prefix)),
+            );
+
+            if let Some(index) = commented {
+                lines[index].remove(0);
+                return Ok(());
             }
+            // Not listed in locale.gen, take the charset from the supported ones.
+            let supported = fs::read_to_string("/usr/share/i18n/SUPPORTED").unwrap();
+            let line = supported
+                .lines()
+                .find(|line| line.starts_with(&prefix))
+                .context(errors::ConfigInvalidSnafu {
+                    desc: format!("unsupported locale {:?}", name),
+                })?;
+            lines.push(line.to_owned());
+            Ok(())
         }
 
-        pub(crate) fn install_locales(&mut self) -> &mut Self {
-            let mut buffer = String::new();
-            let mut locale_gen = File::open("/etc/locale.gen").unwrap();
-            locale_gen.read_to_string(&mut buffer).unwrap();
-            buffer = buffer.replace("#en_US.UTF-8", "en_US.UTF-8");
-            locale_gen = File::create("/etc/locale.gen").unwrap();
-            locale_gen.write_all(buffer.as_bytes()).unwrap();
+        pub(crate) fn install_locales(&mut self) -> ALIResult<&mut Self> {
+            let locale = self.config.locale();
+            let buffer = fs::read_to_string("/etc/locale.gen").unwrap();
+            let mut lines: Vec<String> = buffer.lines().map(|line| line.to_owned()).collect();
 
-            Command::new("locale-gen").spawn().unwrap().wait().unwrap();
+            for name in locale.locales() {
+                Self::enable_locale(&mut lines, name)?;
+            }
+            lines.push(String::new());
+            fs::write("/etc/locale.gen", lines.join("\n")).unwrap();
 
-            File::create("/etc/locale.conf")
-                .unwrap()
-                .write_all("LANG=en_US.UTF-8\n".as_bytes())
-                .unwrap();
-            self
+            let status = Command::new("locale-gen").spawn().unwrap().wait().unwrap();
+            assert!(status.success());
+
+            let mut locale_conf = format!("LANG={}\n", locale.lang());
+
+            for (key, value) in locale.overrides() {
+                locale_conf.push_str(&format!("{}={}\n", key, value));
+            }
+            fs::write("/etc/locale.conf", locale_conf).unwrap();
+            Ok(self)
         }
 
-        pub(crate) fn set_hostname(&mut self) -> &mut Self {
-            File::create("/etc/hostname")
-                .unwrap()
-                .write_all(self.config.system().arch_host().as_bytes())
-                .unwrap();
+        pub(crate) fn configure_console(&mut self) -> &mut Self {
+            let locale = self.config.locale();
+            let mut vconsole = String::new();
+
+            if !locale.keymap().is_empty() {
+                vconsole.push_str(&format!("KEYMAP={}\n", locale.keymap()));
+            }
+            if !locale.font().is_empty() {
+                vconsole.push_str(&format!("FONT={}\n", locale.font()));
+            }
+            if !vconsole.is_empty() {
+                fs::write("/etc/vconsole.conf", vconsole).unwrap();
+            }
             self
         }
 
-        pub(crate) fn set_timezone(&mut self) -> &mut Self {
-            if let Err(e) = symlink(self.config.system().timezone(), "/etc/localtime") {
-                error!("{}", e);
+        /// Writes the file generated by `localectl set-x11-keymap`, which
+        /// can't be used without running systemd.
+        pub(crate) fn configure_x11_keyboard(&mut self) -> &mut Self {
+            let locale = self.config.locale();
+
+            if locale.x11_layout().is_empty() {
+                return self;
+            }
+            let options = [
+                ("XkbLayout", locale.x11_layout()),
+                ("XkbModel", locale.x11_model()),
+                ("XkbVariant", locale.x11_variant()),
+                ("XkbOptions", locale.x11_options()),
+            ];
+            let mut buffer = String::from(concat!(
+                "Section \"InputClass\"\n",
+                "        Identifier \"system-keyboard\"\n",
+                "        MatchIsKeyboard \"on\"\n",
+            ));
+
+            for (option, value) in options.iter().filter(|(_, value)| !value.is_empty()) {
+                buffer.push_str(&format!("        Option \"{}\" \"{}\"\n", option, value));
             }
+            buffer.push_str("EndSection\n");
 
-            Command::new("timedatectl")
-                .args(&["set-ntp", "true"])
-                .spawn()
-                .unwrap()
-                .wait()
-                .unwrap();
+            create_dir_all("/etc/X11/xorg.conf.d").unwrap();
+            fs::write("/etc/X11/xorg.conf.d/00-keyboard.conf", buffer).unwrap();
             self
         }
 
-        pub(crate) fn install_grub(&mut self) -> &mut Self {
-            let mut grub = Command::new("grub-install");
-            let status: ExitStatus;
-
-            if self.efi {
-                pacman_install(&["efibootmgr"]);
-                let id = format!(
-                    "--bootloader-id={}",
-                    self.config.partitions().efi.bootloader_id
-                );
-
-                status = grub
-                    .args(&[
-                        &id,
-                        "--target=x86_64-efi",
-                        "--efi-directory=/efi",
-                        "--boot-directory=/boot",
-                        "--recheck",
-                    ])
-                    .spawn()
-                    .unwrap()
-                    .wait()
-                    .unwrap();
-            } else {
-                status = grub
-                    .arg("--target=i386-pc")
-                    .arg(&self.config.partitions().root.device)
-                    .spawn()
-                    .unwrap()
-                    .wait()
-                    .unwrap();
+        pub(crate) fn set_hostname(&mut self) -> &mut Self {
+            let system = self.config.system();
+            let hostname = format!("{}\n", system.arch_host());
+            fs::write("/etc/hostname", hostname).unwrap();
+
+            let mut names = system.fqdn();
+
+            if !system.domain().is_empty() {
+                names = format!("{} {}", names, system.arch_host());
             }
-            assert!(status.success());
+            let hosts = format!("127.0.0.1 localhost\n::1 localhost\n127.0.1.1 {}\n", names);
+            fs::write("/etc/hosts", hosts).unwrap();
             self
         }
 
-        pub(crate) fn configure_grub(&mut self) -> &mut Self {
-            // TODO: fix grub
-            let uuid = self.get_uuid();
-            let cmdline = self.grub_cmdline(&uuid);
-            let mut buffer = String::new();
+        fn edit_nsswitch_hosts<F: FnOnce(&mut Vec<String>)>(&self, edit: F) {
+            let buffer = fs::read_to_string("/etc/nsswitch.conf").unwrap();
+            let mut lines: Vec<String> = buffer.lines().map(|line| line.to_owned()).collect();
 
-            File::open("/etc/default/grub")
-                .unwrap()
-                .read_to_string(&mut buffer)
+            // hosts: mymachines resolve [!UNAVAIL=return] files myhostname dns
+            let line = lines
+                .iter_mut()
+                .find(|line| line.starts_with("hosts:"))
                 .unwrap();
 
-            buffer = buffer.replace("loglevel=3 quiet", &cmdline);
+            let mut sources: Vec<String> = line["hosts:".len()..]
+                .split_whitespace()
+                .map(|source| source.to_owned())
+                .collect();
 
-            File::create("/etc/default/grub")
-                .unwrap()
-                .write_all(buffer.as_bytes())
-                .unwrap();
+            edit(&mut sources);
+            *line = format!("hosts: {}", sources.join(" "));
+            lines.push(String::new());
+            fs::write("/etc/nsswitch.conf", lines.join("\n")).unwrap();
+        }
 
-            Command::new("grub-mkconfig")
-                .args(&["-o", "/boot/grub/grub.cfg"])
+        pub(crate) fn configure_name_resolution(&mut self) -> ALIResult<&mut Self> {
+            let system = self.config.system();
+
+            if system.nss_myhostname() {
+                self.edit_nsswitch_hosts(|sources| {
+                    if sources.iter().any(|source| source == "myhostname") {
+                        return;
+                    }
+                    let index = sources
+                        .iter()
+                        .position(|source| source == "files")
+                        .map_or(sources.len(), |index| index + 1);
+                    sources.insert(index, "myhostname".to_owned());
+                });
+            }
+            if system.mdns() {
+                pacman_install(&["avahi", "nss-mdns"])?;
+                Service("avahi-daemon").enable();
+
+                self.edit_nsswitch_hosts(|sources| {
+                    if sources.iter().any(|source| source.starts_with("mdns")) {
+                        return;
+                    }
+                    let index = sources
+                        .iter()
+                        .position(|source| source == "resolve" || source == "dns")
+                        .unwrap_or(sources.len());
+                    sources.insert(index, "[NOTFOUND=return]".to_owned());
+                    sources.insert(index, "mdns_minimal".to_owned());
+                });
+            }
+            Ok(self)
+        }
+
+        pub(crate) fn set_timezone(&mut self) -> ALIResult<&mut Self> {
+            let timezone = self.config.system().timezone();
+            let localtime = Path::new("/etc/localtime");
+
+            if localtime.symlink_metadata().is_ok() {
+                fs::remove_file(localtime).context(errors::TimezoneSnafu {
+                    timezone: timezone.clone(),
+                })?;
+            }
+            symlink(&timezone, localtime).context(errors::TimezoneSnafu { timezone })?;
+            Ok(self)
+        }
+
+        pub(crate) fn set_hardware_clock(&mut self) -> &mut Self {
+            let mode = match self.config.system().hardware_clock() {
+                HardwareClock::Utc => "--utc",
+                HardwareClock::Localtime => "--localtime",
+            };
+            let status = Command::new("hwclock")
+                .args(&["--systohc", mode])
                 .spawn()
                 .unwrap()
                 .wait()
                 .unwrap();
+
+            assert!(status.success());
             self
         }
 
-        pub(crate) fn set_mkinitcpio_hooks(&mut self) -> &mut Self {
-            let mut buffer = String::new();
+        /// Enables the time synchronization service, `timedatectl set-ntp`
+        /// can't be used because systemd isn't running in chroot.
+        pub(crate) fn configure_time_sync(&mut self) -> ALIResult<&mut Self> {
+            match self.config.system().time_sync() {
+                TimeSync::None => (),
+                TimeSync::Timesyncd => Service("systemd-timesyncd").enable(),
+                TimeSync::Chrony => {
+                    pacman_install(&["chrony"])?;
+                    Service("chronyd").enable();
+                }
+            }
+            Ok(self)
+        }
 
-            File::open("/etc/mkinitcpio.conf")
-                .unwrap()
-                .read_to_string(&mut buffer)
-                .unwrap();
+        pub(crate) fn configure_reflector(&mut self) -> &mut Self {
+            let mirrors = self.config.mirrors();
 
-            let hooks_source = vec![
-                "base",
-                "udev",
-                "autodetect",
-                "modconf",
-                "block",
-                "filesystems",
-                "keyboard",
-                "fsck",
-            ];
-            let mut hooks_target = Vec::from(&hooks_source[0..5]);
-            hooks_target.push("keyboard");
+            if !mirrors.reflector_timer() {
+                return self;
+            }
+            let mut buffer = String::from("--save /etc/pacman.d/mirrorlist\n");
 
-            if self.config.partitions().root.encryption {
-                hooks_target.push("encrypt");
+            for (option, value) in mirrors.reflector_args() {
+                buffer.push_str(&format!("{} {}\n", option, value));
             }
-            hooks_target.push("filesystems");
-            hooks_target.push("fsck");
+            fs::write("/etc/xdg/reflector/reflector.conf", buffer).unwrap();
+            Service("reflector.timer").enable();
+            self
+        }
 
-            let format_hooks = |hooks: Vec<&str>| format!("HOOKS=({})", hooks.join(" "));
-            let hooks_source = format_hooks(hooks_source);
-            let hooks_target = format_hooks(hooks_target);
-            buffer = buffer.replace(&hooks_source, &hooks_target);
+        fn remove_stale_boot_entries(&self) {
+            let id = &self.config.partitions().efi.bootloader_id;
 
-            File::create("/etc/mkinitcpio.conf")
-                .unwrap()
-                .write_all(buffer.as_bytes())
-                .unwrap();
+            efibootmgr::boot_entries()
+                .iter()
+                .filter(|entry| &entry.label == id)
+                .for_each(|entry| efibootmgr::delete_entry(&entry.number));
+        }
 
-            Command::new("mkinitcpio")
-                .args(&["-p", "linux"])
-                .spawn()
-                .unwrap()
-                .wait()
-                .unwrap();
-            self
+        fn move_boot_entry_first(&self) -> ALIResult<()> {
+            let id = &self.config.partitions().efi.bootloader_id;
+            let entry = efibootmgr::boot_entries()
+                .into_iter()
+                .find(|entry| &entry.label == id)
+                .context(errors::BootEntryNotFoundSnafu { label: id })?;
+
+            let mut order = efibootmgr::boot_order();
+            order.retain(|number| number != &entry.number);
+            order.insert(0, entry.number);
+            efibootmgr::set_boot_order(&order);
+            Ok(())
         }
 
-        pub(crate) fn add_user(&mut self) -> &mut Self {
-            let user = self.config.system().arch_username();
+        fn grub_install_efi(&self, removable: bool) {
+            let id = format!(
+                "--bootloader-id={}",
+                self.config.partitions().efi.bootloader_id
+            );
+            let mut grub = Command::new("grub-install");
 
-            Command::new("useradd")
-                .args(&["-m", user])
-                .spawn()
-                .unwrap()
-                .wait()
-                .unwrap();
+            grub.args(&[
+                &id,
+                "--target=x86_64-efi",
+                "--efi-directory=/efi",
+                "--boot-directory=/boot",
+                "--recheck",
+            ]);
+            if removable {
+                grub.arg("--removable");
+            }
+            let status = grub.spawn().unwrap().wait().unwrap();
+            assert!(status.success());
+        }
+
+        pub(crate) fn install_grub(&mut self) -> ALIResult<&mut Self> {
+            let bootloader = self.config.bootloader();
 
-            let mut password = self.config.system().arch_userpass().to_owned();
+            if !self.efi {
+                let status = Command::new("grub-install")
+                    .arg("--target=i386-pc")
+                    .arg(&self.config.partitions().root.device)
+                    .spawn()
+                    .unwrap()
+                    .wait()
+                    .unwrap();
 
-            if password.is_empty() {
-                println!("Enter a password for the user \"{}\"", user);
-                stdin().read_line(&mut password).unwrap();
+                assert!(status.success());
+                return Ok(self);
             }
-            let mut chpasswd = Command::new("chpasswd")
+            pacman_install(&["efibootmgr"])?;
+
+            if bootloader.remove_stale_entries() {
+                self.remove_stale_boot_entries();
+            }
+            self.grub_install_efi(false);
+
+            // Firmware which forgets NVRAM entries still boots the fallback path.
+            if bootloader.removable() {
+                self.grub_install_efi(true);
+            }
+            if bootloader.boot_first() {
+                self.move_boot_entry_first()?;
+            }
+            Ok(self)
+        }
+
+        fn grub_password_hash(&self, password: &str) -> String {
+            let mut grub_mkpasswd = Command::new("grub-mkpasswd-pbkdf2")
                 .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
                 .spawn()
                 .unwrap();
 
-            let chpasswd_input = format!("{}:{}", user, password);
-            let chpasswd_input = chpasswd_input.as_bytes();
+            let stdin = grub_mkpasswd.stdin.as_mut().unwrap();
 
-            chpasswd
-                .stdin
-                .as_mut()
-                .unwrap()
-                .write_all(chpasswd_input)
-                .unwrap();
+            // The password is asked twice.
+            for _ in 0..2 {
+                stdin.write_all(password.as_bytes()).unwrap();
+                stdin.write_all(b"\n").unwrap();
+            }
+
+            let output = grub_mkpasswd.wait_with_output().unwrap();
+            assert!(output.status.success());
+
+            // PBKDF2 hash of your password is grub.pbkdf2.sha512.10000.XXX
+            let output = String::from_utf8(output.stdout).unwrap();
+            let hash = output.rsplit(' ').next().unwrap().trim();
+            hash.to_owned()
+        }
+
+        /// `buffer` without a previous block between the password markers,
+        /// followed by `block`.
+        fn replace_marked_block(buffer: &str, block: &str) -> String {
+            let mut result = String::new();
+            let mut inside = false;
+
+            for line in buffer.lines() {
+                if line == GRUB_PASSWORD_BEGIN {
+                    inside = true;
+                } else if line == GRUB_PASSWORD_END {
+                    inside = false;
+                } else if !inside {
+                    result.push_str(line);
+                    result.push('\n');
+                }
+            }
+            result.push_str(block);
+            result
+        }
+
+        /// Requires the password to edit entries or use the GRUB console, while
+        /// the generated entries stay bootable without it.
+        pub(crate) fn set_grub_password(&mut self) -> ALIResult<&mut Self> {
+            let password = match self.config.bootloader().password() {
+                Some(password) => password,
+                None => return Ok(self),
+            };
+            let hash = if password.hash.is_empty() {
+                let hash = self.grub_password_hash(&password.password.expose("the GRUB password")?);
+                password.password.clear();
+                hash
+            } else {
+                password.hash.clone()
+            };
+            let block = format!(
+                "{}\nset superusers=\"{}\"\npassword_pbkdf2 {} {}\n{}\n",
+                GRUB_PASSWORD_BEGIN, password.user, password.user, hash, GRUB_PASSWORD_END
+            );
+            let custom =
+                fs::read_to_string(GRUB_CUSTOM_SCRIPT).context(errors::GrubConfigSnafu {
+                    path: GRUB_CUSTOM_SCRIPT,
+                })?;
+
+            // Re-runs replace the block written before.
+            fs::write(
+                GRUB_CUSTOM_SCRIPT,
+                Self::replace_marked_block(&custom, &block),
+            )
+            .and_then(|_| {
+                // The file holds the password hash.
+                fs::set_permissions(GRUB_CUSTOM_SCRIPT, fs::Permissions::from_mode(0o700))
+            })
+            .context(errors::GrubConfigSnafu {
+                path: GRUB_CUSTOM_SCRIPT,
+            })?;
+
+            let mut buffer =
+                fs::read_to_string(GRUB_LINUX_SCRIPT).context(errors::GrubConfigSnafu {
+                    path: GRUB_LINUX_SCRIPT,
+                })?;
+
+            if !buffer.contains("--unrestricted") {
+                let line = buffer
+                    .lines()
+                    .find(|line| line.starts_with(GRUB_CLASS) && line.ends_with('"'))
+                    .context(errors::GrubUnrestrictedSnafu {
+                        path: GRUB_LINUX_SCRIPT,
+                    })?;
+                let unrestricted = format!("{} --unrestricted\"", line.trim_end_matches('"'));
+
+                buffer = buffer.replacen(line, &unrestricted, 1);
+                fs::write(GRUB_LINUX_SCRIPT, buffer).context(errors::GrubConfigSnafu {
+                    path: GRUB_LINUX_SCRIPT,
+                })?;
+            }
+            // Upgrades of grub replace 10_linux.
+            fs::create_dir_all("/etc/pacman.d/hooks")
+                .and_then(|_| fs::write(GRUB_UNRESTRICTED_HOOK, GRUB_UNRESTRICTED_HOOK_CONTENTS))
+                .context(errors::GrubConfigSnafu {
+                    path: GRUB_UNRESTRICTED_HOOK,
+                })?;
+            Ok(self)
+        }
 
-            chpasswd.wait().unwrap();
-            let gitconfig = format!("/home/{}/.gitconfig", user);
+        pub(crate) fn configure_grub(&mut self) -> ALIResult<&mut Self> {
+            let mut grub = ShellConf::open("/etc/default/grub");
+            grub.set_value("GRUB_CMDLINE_LINUX_DEFAULT", &self.grub_cmdline()?);
 
-            fs::rename("/root/installer/gitconfig", &gitconfig).unwrap();
+            if let Some(console) = self.config.system().serial_console() {
+                grub.set_value("GRUB_TERMINAL", "console serial");
+                grub.set_value("GRUB_SERIAL_COMMAND", &console.grub_command());
+            }
+            grub.save();
 
-            Command::new("chown")
-                .args(&[&format!("{}:", user), gitconfig.as_str()])
+            let status = Command::new("grub-mkconfig")
+                .args(&["-o", "/boot/grub/grub.cfg"])
                 .spawn()
                 .unwrap()
                 .wait()
                 .unwrap();
+
+            assert!(status.success());
+            Ok(self)
+        }
+
+        pub(crate) fn enable_serial_getty(&mut self) -> &mut Self {
+            if let Some(console) = self.config.system().serial_console() {
+                Service(&format!("serial-getty@{}", console.device)).enable();
+            }
             self
         }
 
-        pub(crate) fn configure_packages(&mut self) -> &mut Self {
-            PackageConfigurator::new(self.config).run();
+        pub(crate) fn configure_mkinitcpio(&mut self) -> &mut Self {
+            MkinitcpioConfigurator::new(self.config).run();
             self
         }
+
+        pub(crate) fn add_users(&mut self) -> ALIResult<&mut Self> {
+            UserConfigurator::new(self.config).run()?;
+            Ok(self)
+        }
+
+        pub(crate) fn configure_ssh(&mut self) -> ALIResult<&mut Self> {
+            SshConfigurator::new(self.config).run()?;
+            Ok(self)
+        }
+
+        pub(crate) fn configure_network(&mut self) -> ALIResult<&mut Self> {
+            NetworkConfigurator::new(self.config).run()?;
+            Ok(self)
+        }
+
+        pub(crate) fn configure_packages(&mut self) -> ALIResult<&mut Self> {
+            PackageConfigurator::new(self.config).run()?;
+            Ok(self)
+        }
     }
 }
 
 pub fn main(config: &Config) -> ALIResult<()> {
     private::ChrootInstaller::new(config)
-        .install_locales()
+        .install_locales()?
+        .configure_console()
+        .configure_x11_keyboard()
         .set_hostname()
-        .set_timezone()
-        .install_grub()
-        .configure_grub()
-        .set_mkinitcpio_hooks()
-        .add_user()
-        .configure_packages();
+        .configure_name_resolution()?
+        .set_timezone()?
+        .set_hardware_clock()
+        .configure_time_sync()?
+        .configure_reflector()
+        .install_grub()?
+        .set_grub_password()?
+        .configure_grub()?
+        .enable_serial_getty()
+        .configure_mkinitcpio()
+        .add_users()?
+        .configure_ssh()?
+        .configure_network()?
+        .configure_packages()?;
 
     stage2_chroot_install::main(config)?;
     Ok(())
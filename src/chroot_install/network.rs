@@ -0,0 +1,277 @@
+use snafu::ResultExt;
+
+use {
+    crate::{
+        config::{zeroize, Config, Interface, NetworkBackend, Wifi},
+        errors::{self, ALIResult},
+        utils::{iwd, Service},
+    },
+    std::{
+        fmt::Write as _,
+        fs,
+        net::IpAddr,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+    },
+};
+
+const NETWORKD_DIR: &str = "/etc/systemd/network";
+const NM_CONNECTIONS_DIR: &str = "/etc/NetworkManager/system-connections";
+
+/// Writes the file, `private` ones may hold PSKs and are readable by root
+/// only.
+fn write_file<P: AsRef<Path>>(path: P, contents: &str, private: bool) -> ALIResult<()> {
+    let path = path.as_ref();
+    let mode = if private { 0o600 } else { 0o644 };
+
+    fs::create_dir_all(path.parent().unwrap())
+        .and_then(|_| fs::write(path, contents))
+        .and_then(|_| fs::set_permissions(path, fs::Permissions::from_mode(mode)))
+        .context(errors::NetworkConfigSnafu { path })
+}
+
+/// Whether the address, optionally with a prefix, is an IPv6 one.
+fn is_ipv6(address: &str) -> bool {
+    let address = address.split('/').next().unwrap();
+    matches!(address.parse(), Ok(IpAddr::V6(_)))
+}
+
+fn psk_name(wifi: &Wifi) -> String {
+    format!("the PSK of wifi {}", wifi.ssid)
+}
+
+pub(crate) struct NetworkConfigurator<'a> {
+    config: &'a Config,
+}
+
+impl<'a> NetworkConfigurator<'a> {
+    pub(crate) fn new(config: &'a Config) -> NetworkConfigurator<'a> {
+        NetworkConfigurator { config }
+    }
+
+    fn networkd_unit(&self, interface: &Interface) -> String {
+        let mut buffer = String::from("[Match]\n");
+
+        if let Some(ref name) = interface.name {
+            writeln!(buffer, "Name={}", name).unwrap();
+        }
+        if let Some(ref mac) = interface.mac {
+            writeln!(buffer, "MACAddress={}", mac).unwrap();
+        }
+        buffer.push_str("\n[Network]\n");
+
+        if interface.dhcp() {
+            buffer.push_str("DHCP=yes\n");
+        }
+        for address in &interface.addresses {
+            writeln!(buffer, "Address={}", address).unwrap();
+        }
+        if let Some(ref gateway) = interface.gateway {
+            writeln!(buffer, "Gateway={}", gateway).unwrap();
+        }
+        for dns in &interface.dns {
+            writeln!(buffer, "DNS={}", dns).unwrap();
+        }
+        buffer
+    }
+
+    fn configure_networkd(&self) -> ALIResult<()> {
+        let network = self.config.network();
+
+        for (index, interface) in network.interfaces().iter().enumerate() {
+            let mut path = PathBuf::from(NETWORKD_DIR);
+            path.push(format!("20-installer-{}.network", index));
+            write_file(path, &self.networkd_unit(interface), false)?;
+        }
+        Service("systemd-networkd").enable();
+
+        if !network.wifi().is_empty() {
+            // Addresses come from networkd, iwd only associates. Units of
+            // the listed interfaces sort first and take precedence.
+            write_file(
+                "/etc/iwd/main.conf",
+                "[General]\nEnableNetworkConfiguration=false\n",
+                false,
+            )?;
+            write_file(
+                format!("{}/30-installer-wlan.network", NETWORKD_DIR),
+                "[Match]\nType=wlan\n\n[Network]\nDHCP=yes\n",
+                false,
+            )?;
+            self.write_iwd_profiles()?;
+            Service("iwd").enable();
+        }
+        Ok(())
+    }
+
+    fn write_iwd_profiles(&self) -> ALIResult<()> {
+        for wifi in self.config.network().wifi() {
+            let secured = wifi.psk.is_set();
+            let mut buffer = if secured {
+                iwd::profile(&wifi.psk.expose(&psk_name(wifi))?, wifi.hidden)
+            } else {
+                iwd::profile("", wifi.hidden)
+            };
+            wifi.psk.clear();
+
+            let path = iwd::profile_path(iwd::PROFILES_DIR, &wifi.ssid, secured);
+            write_file(path, &buffer, true)?;
+            zeroize(&mut buffer);
+        }
+        Ok(())
+    }
+
+    fn configure_iwd(&self) -> ALIResult<()> {
+        let resolved = self.config.network().resolved().enable;
+        let mut main_conf = String::from("[General]\nEnableNetworkConfiguration=true\n");
+
+        if resolved {
+            main_conf.push_str("\n[Network]\nNameResolvingService=systemd\n");
+        }
+        write_file("/etc/iwd/main.conf", &main_conf, false)?;
+        self.write_iwd_profiles()?;
+        Service("iwd").enable();
+        Ok(())
+    }
+
+    /// `[ipv4]` and `[ipv6]` of a profile, static addresses, the gateway
+    /// and DNS servers go to the section of their family.
+    fn nm_ip_sections(&self, interface: Option<&Interface>) -> String {
+        let mut buffer = String::new();
+
+        for &(section, ipv6) in &[("ipv4", false), ("ipv6", true)] {
+            let same_family = |address: &&String| is_ipv6(address) == ipv6;
+            let (addresses, gateway): (Vec<_>, _) = match interface {
+                Some(interface) if !interface.dhcp() => (
+                    interface.addresses.iter().filter(same_family).collect(),
+                    interface.gateway.as_ref().filter(same_family),
+                ),
+                _ => (vec![], None),
+            };
+            let method = match interface {
+                _ if !addresses.is_empty() => "manual",
+                // Only static addresses of the other family.
+                Some(interface) if !interface.dhcp() && !ipv6 => "disabled",
+                _ => "auto",
+            };
+            writeln!(buffer, "\n[{}]\nmethod={}", section, method).unwrap();
+
+            for (index, address) in addresses.iter().enumerate() {
+                write!(buffer, "address{}={}", index + 1, address).unwrap();
+
+                if let Some(gateway) = gateway {
+                    write!(buffer, ",{}", gateway).unwrap();
+                }
+                buffer.push('\n');
+            }
+            // NetworkManager rejects DNS servers of a disabled family.
+            if let Some(interface) = interface.filter(|_| method != "disabled") {
+                let dns: Vec<_> = interface
+                    .dns
+                    .iter()
+                    .filter(same_family)
+                    .map(|dns| &**dns)
+                    .collect();
+
+                if !dns.is_empty() {
+                    writeln!(buffer, "dns={};", dns.join(";")).unwrap();
+                }
+            }
+        }
+        buffer
+    }
+
+    fn configure_networkmanager(&self) -> ALIResult<()> {
+        let network = self.config.network();
+
+        for (index, interface) in network.interfaces().iter().enumerate() {
+            let id = format!("installer-{}", index);
+            let mut buffer = format!("[connection]\nid={}\ntype=ethernet\n", id);
+
+            if let Some(ref name) = interface.name {
+                writeln!(buffer, "interface-name={}", name).unwrap();
+            }
+            if let Some(ref mac) = interface.mac {
+                writeln!(buffer, "\n[ethernet]\nmac-address={}", mac).unwrap();
+            }
+            buffer.push_str(&self.nm_ip_sections(Some(interface)));
+
+            let mut path = PathBuf::from(NM_CONNECTIONS_DIR);
+            path.push(format!("{}.nmconnection", id));
+            write_file(path, &buffer, true)?;
+        }
+        for (index, wifi) in network.wifi().iter().enumerate() {
+            let id = format!("installer-wifi-{}", index);
+            let mut buffer = format!(
+                "[connection]\nid={}\ntype=wifi\n\n[wifi]\nmode=infrastructure\nssid={}\n",
+                id, wifi.ssid
+            );
+
+            if wifi.hidden {
+                buffer.push_str("hidden=true\n");
+            }
+            if wifi.psk.is_set() {
+                writeln!(
+                    buffer,
+                    "\n[wifi-security]\nkey-mgmt=wpa-psk\npsk={}",
+                    &*wifi.psk.expose(&psk_name(wifi))?
+                )
+                .unwrap();
+                wifi.psk.clear();
+            }
+            buffer.push_str(&self.nm_ip_sections(None));
+
+            let mut path = PathBuf::from(NM_CONNECTIONS_DIR);
+            path.push(format!("{}.nmconnection", id));
+            write_file(path, &buffer, true)?;
+            zeroize(&mut buffer);
+        }
+        Service("NetworkManager").enable();
+        Ok(())
+    }
+
+    fn configure_resolved(&self) -> ALIResult<()> {
+        let resolved = self.config.network().resolved();
+        let mut buffer = String::from("[Resolve]\n");
+
+        if !resolved.dns.is_empty() {
+            writeln!(buffer, "DNS={}", resolved.dns.join(" ")).unwrap();
+        }
+        if !resolved.fallback_dns.is_empty() {
+            writeln!(buffer, "FallbackDNS={}", resolved.fallback_dns.join(" ")).unwrap();
+        }
+        if !resolved.domains.is_empty() {
+            writeln!(buffer, "Domains={}", resolved.domains.join(" ")).unwrap();
+        }
+        if let Some(ref dnssec) = resolved.dnssec {
+            writeln!(buffer, "DNSSEC={}", dnssec).unwrap();
+        }
+        if let Some(ref dns_over_tls) = resolved.dns_over_tls {
+            writeln!(buffer, "DNSOverTLS={}", dns_over_tls).unwrap();
+        }
+        write_file(
+            "/etc/systemd/resolved.conf.d/10-installer.conf",
+            &buffer,
+            false,
+        )?;
+        // /etc/resolv.conf is linked to the stub after leaving the chroot,
+        // arch-chroot mounts the one of the live system over it.
+        Service("systemd-resolved").enable();
+        Ok(())
+    }
+
+    pub(crate) fn run(&mut self) -> ALIResult<()> {
+        let network = self.config.network();
+
+        match network.backend() {
+            NetworkBackend::None => (),
+            NetworkBackend::Networkd => self.configure_networkd()?,
+            NetworkBackend::NetworkManager => self.configure_networkmanager()?,
+            NetworkBackend::Iwd => self.configure_iwd()?,
+        }
+        if network.resolved().enable {
+            self.configure_resolved()?;
+        }
+        Ok(())
+    }
+}
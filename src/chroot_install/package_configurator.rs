@@ -1,6 +1,7 @@
-use {
-    crate::{config::Config, utils::Service},
-    std::{fs, io::prelude::*},
+use crate::{
+    config::{Config, NetworkBackend, SudoPolicy},
+    errors::ALIResult,
+    utils::{sudoers, Service},
 };
 
 pub(crate) struct PackageConfigurator<'a> {
@@ -12,30 +13,41 @@ impl<'a> PackageConfigurator<'a> {
         PackageConfigurator { config }
     }
 
-    pub(crate) fn run(&mut self) {
+    pub(crate) fn run(&mut self) -> ALIResult<()> {
         let packages = self.config.packages().pacman_system();
 
         if packages.contains(&"sudo") {
-            self.configure_sudo();
+            self.configure_sudo()?;
         }
-        if packages.contains(&"dhcpcd") {
-            self.configure_dhcpcd();
-        }
-        if packages.contains(&"networkmanager") {
-            self.configure_networkmanager();
+        // Otherwise the services come from the `[network]` section.
+        if self.config.network().backend() == NetworkBackend::None {
+            if packages.contains(&"dhcpcd") {
+                self.configure_dhcpcd();
+            }
+            if packages.contains(&"networkmanager") {
+                self.configure_networkmanager();
+            }
         }
+        Ok(())
     }
 
-    fn configure_sudo(&mut self) -> &mut Self {
-        let buffer = format!("{} ALL=(ALL) ALL\n", self.config.system().arch_username());
+    fn configure_sudo(&mut self) -> ALIResult<&mut Self> {
+        let mut buffer = String::new();
+
+        for user in self.config.users() {
+            match user.sudo {
+                SudoPolicy::None => (),
+                SudoPolicy::Password => buffer.push_str(&format!("{} ALL=(ALL) ALL\n", user.name)),
+                SudoPolicy::Nopasswd => {
+                    buffer.push_str(&format!("{} ALL=(ALL) NOPASSWD: ALL\n", user.name))
+                }
+            }
+        }
 
-        fs::OpenOptions::new()
-            .append(true)
-            .open("/etc/sudoers")
-            .unwrap()
-            .write_all(buffer.as_bytes())
-            .unwrap();
-        self
+        if !buffer.is_empty() {
+            sudoers::install_drop_in("10-installer-users", &buffer)?;
+        }
+        Ok(self)
     }
 
     fn configure_dhcpcd(&mut self) -> &mut Self {
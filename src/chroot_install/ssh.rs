@@ -0,0 +1,178 @@
+use snafu::{ensure, ResultExt};
+
+use {
+    crate::{
+        config::Config,
+        errors::{self, ALIResult},
+        utils::{command::Command, Service},
+    },
+    log::info,
+    std::{env, fs, path::Path},
+};
+
+const SSHD_CONFIG: &str = "/etc/ssh/sshd_config";
+const DROP_IN: &str = "/etc/ssh/sshd_config.d/10-installer.conf";
+const INCLUDE: &str = "Include /etc/ssh/sshd_config.d/*.conf";
+
+pub(crate) struct SshConfigurator<'a> {
+    config: &'a Config,
+}
+
+impl<'a> SshConfigurator<'a> {
+    pub(crate) fn new(config: &'a Config) -> SshConfigurator<'a> {
+        SshConfigurator { config }
+    }
+
+    /// sshd takes the first value it reads, so the drop-ins have to be
+    /// included before any other option.
+    fn ensure_include(&self) -> ALIResult<()> {
+        let buffer = fs::read_to_string(SSHD_CONFIG)
+            .context(errors::SshConfigSnafu { path: SSHD_CONFIG })?;
+
+        if buffer.lines().any(|line| line.trim() == INCLUDE) {
+            return Ok(());
+        }
+        fs::write(SSHD_CONFIG, format!("{}\n\n{}", INCLUDE, buffer))
+            .context(errors::SshConfigSnafu { path: SSHD_CONFIG })
+    }
+
+    fn write_drop_in(&self) -> ALIResult<()> {
+        let ssh = self.config.ssh();
+        let password = if ssh.password_authentication() {
+            "yes"
+        } else {
+            "no"
+        };
+        let buffer = format!(
+            "Port {}\n\
+             PermitRootLogin {}\n\
+             PasswordAuthentication {}\n\
+             KbdInteractiveAuthentication {}\n\
+             PubkeyAuthentication yes\n\
+             PermitEmptyPasswords no\n",
+            ssh.port(),
+            ssh.permit_root_login().as_str(),
+            password,
+            password,
+        );
+        let path = Path::new(DROP_IN);
+
+        fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| fs::write(path, buffer))
+            .context(errors::SshConfigSnafu { path })
+    }
+
+    fn check(&self, host_key: Option<&Path>) -> ALIResult<()> {
+        let mut sshd = Command::new("sshd");
+        sshd.arg("-t");
+
+        if let Some(host_key) = host_key {
+            sshd.arg("-h").arg(host_key);
+        }
+        let status = sshd.spawn().unwrap().wait().unwrap();
+
+        ensure!(
+            status.success(),
+            errors::CommandExecutionSnafu {
+                message: sshd.command_line(),
+            }
+        );
+        Ok(())
+    }
+
+    /// Checks the config with a throwaway host key, sshd generates the real
+    /// ones on first start.
+    fn check_with_temporary_key(&self) -> ALIResult<()> {
+        let dir = env::temp_dir().join("installer-sshd-check");
+        let key = dir.join("ssh_host_ed25519_key");
+
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut keygen = Command::new("ssh-keygen");
+        let status = keygen
+            .args(&["-q", "-t", "ed25519", "-N", "", "-f"])
+            .arg(&key)
+            .spawn()
+            .unwrap()
+            .wait()
+            .unwrap();
+
+        let checked = if status.success() {
+            self.check(Some(&key))
+        } else {
+            errors::CommandExecutionSnafu {
+                message: keygen.command_line(),
+            }
+            .fail()
+        };
+        fs::remove_dir_all(&dir).unwrap();
+        checked
+    }
+
+    fn generate_host_keys(&self) -> ALIResult<()> {
+        let status = Command::new("ssh-keygen")
+            .arg("-A")
+            .spawn()
+            .unwrap()
+            .wait()
+            .unwrap();
+
+        ensure!(
+            status.success(),
+            errors::CommandExecutionSnafu {
+                message: "ssh-keygen -A".to_owned(),
+            }
+        );
+        let mut keys: Vec<_> = fs::read_dir("/etc/ssh")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                let name = path.file_name().unwrap().to_str().unwrap();
+                name.starts_with("ssh_host_") && name.ends_with("_key.pub")
+            })
+            .collect();
+        keys.sort();
+
+        for key in keys {
+            let output = Command::new("ssh-keygen")
+                .arg("-lf")
+                .arg(&key)
+                .output()
+                .unwrap();
+
+            ensure!(
+                output.status.success(),
+                errors::CommandExecutionSnafu {
+                    message: format!("ssh-keygen -lf {:?}", key),
+                }
+            );
+            let fingerprint = String::from_utf8(output.stdout).unwrap();
+            let fingerprint = fingerprint.trim();
+            info!("host key {}", fingerprint);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn run(&mut self) -> ALIResult<()> {
+        let ssh = self.config.ssh();
+
+        if !ssh.enable() {
+            return Ok(());
+        }
+        self.ensure_include()?;
+        self.write_drop_in()?;
+
+        // The check fails without host keys.
+        if ssh.generate_host_keys() {
+            self.generate_host_keys()?;
+            self.check(None)?;
+        } else {
+            self.check_with_temporary_key()?;
+        }
+        Service("sshd").enable();
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use snafu::ensure;
+
+use {
+    crate::{
+        config::{Config, Secret, User},
+        errors::{self, ALIResult},
+        utils::command::Command,
+    },
+    std::{
+        fs::{self, create_dir_all},
+        io::prelude::*,
+        os::unix::fs::PermissionsExt,
+        path::Path,
+        process::Stdio,
+    },
+};
+
+fn chpasswd(user: &str, password: &str, encrypted: bool) -> ALIResult<()> {
+    let mut chpasswd = Command::new("chpasswd");
+
+    if encrypted {
+        chpasswd.arg("--encrypted");
+    }
+    let mut chpasswd = chpasswd.stdin(Stdio::piped()).spawn().unwrap();
+    let stdin = chpasswd.stdin.as_mut().unwrap();
+
+    // Written piecewise so that no other copy of the password is made.
+    stdin.write_all(format!("{}:", user).as_bytes()).unwrap();
+    stdin
+        .write_all(password.trim_end_matches('\n').as_bytes())
+        .unwrap();
+    stdin.write_all(b"\n").unwrap();
+
+    let status = chpasswd.wait().unwrap();
+
+    ensure!(
+        status.success(),
+        errors::CommandExecutionSnafu {
+            message: format!("chpasswd {}", user),
+        }
+    );
+    Ok(())
+}
+
+/// Sets the password, either plaintext or hash, whichever is configured.
+pub(crate) fn set_password(user: &str, password: &Secret, hash: &str) -> ALIResult<()> {
+    if !hash.is_empty() {
+        return chpasswd(user, hash, true);
+    }
+    let result = chpasswd(
+        user,
+        &password.expose(&format!("the password of user {}", user))?,
+        false,
+    );
+    password.clear();
+    result
+}
+
+pub(crate) struct UserConfigurator<'a> {
+    config: &'a Config,
+}
+
+impl<'a> UserConfigurator<'a> {
+    pub(crate) fn new(config: &'a Config) -> UserConfigurator<'a> {
+        UserConfigurator { config }
+    }
+
+    fn group_exists(group: &str) -> bool {
+        Command::new("getent")
+            .args(&["group", group])
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap()
+            .wait()
+            .unwrap()
+            .success()
+    }
+
+    fn create_groups(&self, groups: &[String]) -> ALIResult<()> {
+        // Groups like docker or libvirt appear only with their packages, the
+        // others are regular groups of the users.
+        for group in groups {
+            if Self::group_exists(group) {
+                continue;
+            }
+            let status = Command::new("groupadd")
+                .arg(group)
+                .spawn()
+                .unwrap()
+                .wait()
+                .unwrap();
+
+            ensure!(
+                status.success(),
+                errors::CommandExecutionSnafu {
+                    message: format!("groupadd {}", group),
+                }
+            );
+        }
+        Ok(())
+    }
+
+    fn create_user(&self, user: &User) -> ALIResult<()> {
+        let mut useradd = Command::new("useradd");
+        useradd.arg("--create-home");
+
+        if let Some(uid) = user.uid {
+            useradd.args(&["--uid", &uid.to_string()]);
+        }
+        if let Some(ref shell) = user.shell {
+            useradd.args(&["--shell", shell]);
+        }
+        if !user.groups.is_empty() {
+            useradd.args(&["--groups", &user.groups.join(",")]);
+        }
+        let status = useradd.arg(&user.name).spawn().unwrap().wait().unwrap();
+
+        ensure!(
+            status.success(),
+            errors::CommandExecutionSnafu {
+                message: format!("useradd {}", user.name),
+            }
+        );
+        Ok(())
+    }
+
+    fn chown<P: AsRef<Path>>(&self, user: &str, path: P) -> ALIResult<()> {
+        let status = Command::new("chown")
+            .args(&["-R", &format!("{}:", user)])
+            .arg(path.as_ref())
+            .spawn()
+            .unwrap()
+            .wait()
+            .unwrap();
+
+        ensure!(
+            status.success(),
+            errors::CommandExecutionSnafu {
+                message: format!("chown {} {:?}", user, path.as_ref()),
+            }
+        );
+        Ok(())
+    }
+
+    fn write_authorized_keys(&self, user: &str, home: &Path, keys: &[String]) -> ALIResult<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let ssh_dir = home.join(".ssh");
+        create_dir_all(&ssh_dir).unwrap();
+        fs::set_permissions(&ssh_dir, fs::Permissions::from_mode(0o700)).unwrap();
+
+        let authorized_keys = ssh_dir.join("authorized_keys");
+
+        let mut buffer = keys.join("\n");
+        buffer.push('\n');
+        fs::write(&authorized_keys, buffer).unwrap();
+        fs::set_permissions(&authorized_keys, fs::Permissions::from_mode(0o600)).unwrap();
+
+        self.chown(user, &ssh_dir)
+    }
+
+    fn copy_gitconfig(&self) -> ALIResult<()> {
+        let user = self.config.primary_user();
+        let mut gitconfig = user.home();
+        gitconfig.push(".gitconfig");
+
+        fs::rename("/root/installer/gitconfig", &gitconfig).unwrap();
+        self.chown(&user.name, &gitconfig)
+    }
+
+    fn configure_root(&self) -> ALIResult<()> {
+        let root = self.config.root();
+
+        if root.password.is_set() || !root.password_hash.is_empty() {
+            set_password("root", &root.password, &root.password_hash)?;
+        }
+        if root.lock {
+            let status = Command::new("passwd")
+                .args(&["--lock", "root"])
+                .spawn()
+                .unwrap()
+                .wait()
+                .unwrap();
+
+            ensure!(
+                status.success(),
+                errors::CommandExecutionSnafu {
+                    message: "passwd --lock root".to_owned(),
+                }
+            );
+        }
+        self.write_authorized_keys("root", Path::new("/root"), &root.authorized_keys)
+    }
+
+    pub(crate) fn run(&mut self) -> ALIResult<()> {
+        for user in self.config.users() {
+            self.create_groups(&user.groups)?;
+            self.create_user(user)?;
+            set_password(&user.name, &user.password, &user.password_hash)?;
+            self.write_authorized_keys(&user.name, &user.home(), &user.authorized_keys)?;
+        }
+        self.copy_gitconfig()?;
+        self.configure_root()
+    }
+}
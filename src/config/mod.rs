@@ -9,7 +9,13 @@ use {
     std::{path::PathBuf, rc::Rc},
 };
 
+mod secret;
 mod toml;
+pub(crate) use self::secret::{staged_path, zeroize, Secret};
+pub(crate) use self::toml::{
+    Connectivity, ConnectivityMethod, HardwareClock, Interface, LiveNetworkKind, NetworkBackend,
+    SerialConsole, SudoPolicy, SwapKind, TimeSync, User, Wifi, WifiMethod,
+};
 mod validator;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -18,8 +24,10 @@ pub enum InstallStep {
     Encrypt,
     Format,
     Mount,
+    Swap,
     Mirrors,
-    Multilib,
+    Keyring,
+    PacmanConf,
     Pacstrap,
     Fstab,
     Chroot,
@@ -34,8 +42,11 @@ impl FromStr for InstallStep {
             "encrypt" => Ok(InstallStep::Encrypt),
             "format" => Ok(InstallStep::Format),
             "mount" => Ok(InstallStep::Mount),
+            "swap" => Ok(InstallStep::Swap),
             "mirrors" => Ok(InstallStep::Mirrors),
-            "multilib" => Ok(InstallStep::Multilib),
+            "keyring" => Ok(InstallStep::Keyring),
+            // Formerly only enabled multilib.
+            "pacman-conf" | "multilib" => Ok(InstallStep::PacmanConf),
             "pacstrap" => Ok(InstallStep::Pacstrap),
             "fstab" => Ok(InstallStep::Fstab),
             "chroot" => Ok(InstallStep::Chroot),
@@ -123,11 +134,16 @@ impl Config {
     }
 
     pub fn validate(&self) -> ALIResult<()> {
-        println!("{:?}", self.toml.partitions);
         validator::ConfigValidator::new(self.toml.clone()).validate()?;
         Ok(())
     }
 
+    /// Checks files of the live system, which the target doesn't have.
+    pub fn validate_live(&self) -> ALIResult<()> {
+        validator::ConfigValidator::new(self.toml.clone()).validate_live()?;
+        Ok(())
+    }
+
     pub(crate) fn partitions(&self) -> &toml::Partitions {
         &self.toml.partitions
     }
@@ -143,4 +159,88 @@ impl Config {
     pub(crate) fn packages(&self) -> &toml::Packages {
         &self.toml.packages
     }
+
+    pub(crate) fn mkinitcpio(&self) -> &toml::Mkinitcpio {
+        &self.toml.mkinitcpio
+    }
+
+    pub(crate) fn swap(&self) -> &toml::Swap {
+        &self.toml.swap
+    }
+
+    pub(crate) fn bootloader(&self) -> &toml::Bootloader {
+        &self.toml.bootloader
+    }
+
+    pub(crate) fn locale(&self) -> &toml::Locale {
+        &self.toml.locale
+    }
+
+    pub(crate) fn users(&self) -> &[User] {
+        &self.toml.users
+    }
+
+    /// The user who owns the dotfiles and builds AUR packages.
+    pub(crate) fn primary_user(&self) -> &User {
+        &self.toml.users[0]
+    }
+
+    pub(crate) fn root(&self) -> &toml::Root {
+        &self.toml.root
+    }
+
+    pub(crate) fn ssh(&self) -> &toml::Ssh {
+        &self.toml.ssh
+    }
+
+    pub(crate) fn network(&self) -> &toml::Network {
+        &self.toml.network
+    }
+
+    pub(crate) fn live_network(&self) -> &toml::LiveNetwork {
+        &self.toml.live_network
+    }
+
+    pub(crate) fn connectivity(&self) -> &toml::Connectivity {
+        &self.toml.connectivity
+    }
+
+    pub(crate) fn offline(&self) -> &toml::Offline {
+        &self.toml.offline
+    }
+
+    pub(crate) fn mirrors(&self) -> &toml::Mirrors {
+        &self.toml.mirrors
+    }
+
+    pub(crate) fn keyring(&self) -> &toml::Keyring {
+        &self.toml.keyring
+    }
+
+    pub(crate) fn pacman(&self) -> &toml::Pacman {
+        &self.toml.pacman
+    }
+
+    pub(crate) fn repositories(&self) -> &[toml::Repository] {
+        &self.toml.repositories
+    }
+
+    pub(crate) fn package_check(&self) -> &toml::PackageCheck {
+        &self.toml.package_check
+    }
+
+    /// Secrets which are used inside the chroot.
+    pub(crate) fn chroot_secrets(&self) -> Vec<&Secret> {
+        let toml = &self.toml;
+        let mut secrets: Vec<_> = toml.users.iter().map(|user| &user.password).collect();
+
+        secrets.push(&toml.root.password);
+        secrets.push(toml.shadowsocks.password());
+
+        if let Some(password) = toml.bootloader.password() {
+            secrets.push(&password.password);
+        }
+        secrets.extend(toml.network.wifi().iter().map(|wifi| &wifi.psk));
+        secrets
+    }
 }
@@ -0,0 +1,157 @@
+use snafu::{OptionExt, ResultExt};
+
+use {
+    crate::{
+        constants::SECRETS_DIR,
+        errors::{self, ALIResult},
+        utils::passwd::read_new_password,
+    },
+    serde::Deserialize,
+    std::{
+        cell::{Ref, RefCell},
+        env, fmt, fs,
+        path::{Path, PathBuf},
+        ptr,
+        sync::atomic::{compiler_fence, Ordering},
+    },
+};
+
+/// Overwrites the buffer with zeroes, the writes can't be optimized away.
+pub(crate) fn zeroize_bytes(bytes: &mut [u8]) {
+    for byte in bytes {
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Overwrites the string with zeroes before releasing it.
+pub(crate) fn zeroize(value: &mut String) {
+    // Safety: zeroes are valid UTF-8.
+    zeroize_bytes(unsafe { value.as_bytes_mut() });
+    value.clear();
+}
+
+/// Copy of the `file:` secret at `path` in the target, see `SECRETS_DIR`.
+pub(crate) fn staged_path(path: &Path) -> PathBuf {
+    Path::new(SECRETS_DIR).join(path.strip_prefix("/").unwrap_or(path))
+}
+
+#[derive(Debug)]
+enum Source {
+    Value,
+    Env(String),
+    File(PathBuf),
+    Prompt,
+}
+
+/// Password or key from the config, which never shows up in `Debug` output.
+///
+/// Besides the plain value, the config may hold `env:VAR`, `file:/path` or
+/// `prompt`, which are resolved on first use. Paths are on the live system,
+/// inside the chroot the staged copy is read instead.
+#[derive(Deserialize)]
+#[serde(from = "String")]
+pub(crate) struct Secret {
+    source: Source,
+    /// Whether the config holds a value, kept after `clear`.
+    set: bool,
+    value: RefCell<Option<String>>,
+}
+
+impl From<String> for Secret {
+    fn from(mut value: String) -> Self {
+        let source = if let Some(var) = value.strip_prefix("env:") {
+            Source::Env(var.to_owned())
+        } else if let Some(path) = value.strip_prefix("file:") {
+            Source::File(PathBuf::from(path))
+        } else if value == "prompt" {
+            Source::Prompt
+        } else {
+            return Secret {
+                source: Source::Value,
+                set: !value.is_empty(),
+                value: RefCell::new(Some(value)),
+            };
+        };
+        zeroize(&mut value);
+        Secret {
+            source,
+            set: true,
+            value: RefCell::new(None),
+        }
+    }
+}
+
+impl Default for Secret {
+    fn default() -> Self {
+        Secret::from(String::new())
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.source {
+            Source::Value => write!(f, "Secret(***)"),
+            Source::Env(ref var) => write!(f, "Secret(env:{})", var),
+            Source::File(ref path) => write!(f, "Secret(file:{})", path.display()),
+            Source::Prompt => write!(f, "Secret(prompt)"),
+        }
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl Secret {
+    /// Whether the secret is configured, also after `clear`. Values from
+    /// other sources are checked only on use.
+    pub(crate) fn is_set(&self) -> bool {
+        self.set
+    }
+
+    /// Path of a `file:` secret.
+    pub(crate) fn file(&self) -> Option<&Path> {
+        match self.source {
+            Source::File(ref path) => Some(path),
+            _ => None,
+        }
+    }
+
+    fn resolve(&self, name: &str) -> ALIResult<String> {
+        match self.source {
+            Source::Value => errors::SecretClearedSnafu { name }.fail(),
+            Source::Env(ref var) => env::var(var).ok().context(errors::SecretEnvSnafu { var }),
+            Source::File(ref path) => {
+                let staged = staged_path(path);
+                let path = if staged.exists() { &staged } else { path };
+                let mut value =
+                    fs::read_to_string(path).context(errors::SecretFileSnafu { path })?;
+                let len = value.trim_end_matches(&['\r', '\n'][..]).len();
+                value.truncate(len);
+                Ok(value)
+            }
+            Source::Prompt => read_new_password(&format!("Enter {}", name)),
+        }
+    }
+
+    /// Returns the value, `name` describes the secret in prompts and errors.
+    pub(crate) fn expose(&self, name: &str) -> ALIResult<Ref<'_, str>> {
+        if self.value.borrow().is_none() {
+            let value = self.resolve(name)?;
+            *self.value.borrow_mut() = Some(value);
+        }
+        Ok(Ref::map(self.value.borrow(), |value| {
+            value.as_deref().unwrap()
+        }))
+    }
+
+    /// Zeroizes the value once it isn't needed anymore.
+    pub(crate) fn clear(&self) {
+        if let Some(mut value) = self.value.borrow_mut().take() {
+            zeroize(&mut value);
+        }
+    }
+}
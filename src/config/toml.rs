@@ -3,11 +3,14 @@ use snafu::ResultExt;
 use crate::errors;
 
 use {
+    super::secret::{zeroize_bytes, Secret},
     crate::{errors::ALIResult, utils::exe_dir},
     serde::Deserialize,
     std::{
+        collections::BTreeMap,
         fs::File,
         io::prelude::*,
+        mem,
         path::{Path, PathBuf},
     },
     toml,
@@ -44,7 +47,7 @@ pub(crate) struct PartitionBoot {
 pub(crate) struct PartitionRoot {
     pub(crate) device: String,
     pub(crate) crypt_mapping: String,
-    pub(crate) password: String,
+    pub(crate) password: Secret,
     pub(crate) encryption: bool,
     pub(crate) fs: String,
 }
@@ -59,38 +62,109 @@ impl PartitionRoot {
 pub(crate) struct System {
     pub(crate) drive: String,
     arch_host: String,
+    #[serde(default)]
     arch_username: String,
-    arch_userpass: String,
+    #[serde(default)]
+    arch_userpass: Secret,
     aur_helper: String,
+    /// Legacy protocol filter, superseded by `[mirrors]`.
+    #[serde(default)]
     mirror_protocol: String,
+    /// Legacy switch, superseded by `[pacman]`.
+    #[serde(default)]
     multilib: bool,
     timezone: PathBuf,
+    /// Legacy way to get online, superseded by `[live_network]`.
+    #[serde(default)]
     pub(crate) connection_command: Vec<String>,
+    #[serde(default)]
+    serial_console: Option<String>,
+    #[serde(default)]
+    hardware_clock: HardwareClock,
+    #[serde(default)]
+    time_sync: TimeSync,
+    #[serde(default)]
+    domain: String,
+    #[serde(default)]
+    nss_myhostname: bool,
+    #[serde(default)]
+    mdns: bool,
 }
 
-impl System {
-    pub(crate) fn arch_host(&self) -> &str {
-        &self.arch_host
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum HardwareClock {
+    #[default]
+    Utc,
+    Localtime,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TimeSync {
+    None,
+    #[default]
+    Timesyncd,
+    Chrony,
+}
+
+/// Serial console in the kernel `console=` format, e.g. `ttyS0,115200`.
+#[derive(Debug)]
+pub(crate) struct SerialConsole<'a> {
+    pub(crate) device: &'a str,
+    pub(crate) speed: Option<&'a str>,
+}
+
+impl<'a> SerialConsole<'a> {
+    fn new(console: &'a str) -> SerialConsole<'a> {
+        let mut parts = console.splitn(2, ',');
+        let device = parts.next().unwrap();
+        let speed = parts.next();
+        SerialConsole { device, speed }
     }
 
-    pub(crate) fn arch_username(&self) -> &str {
-        &self.arch_username
+    /// Port number of `ttySN` devices, as used by GRUB.
+    pub(crate) fn unit(&self) -> Option<u32> {
+        self.device.strip_prefix("ttyS")?.parse().ok()
     }
 
-    pub(crate) fn arch_userpass(&self) -> &str {
-        &self.arch_userpass
+    pub(crate) fn kernel_params(&self) -> Vec<String> {
+        let mut console = format!("console={}", self.device);
+
+        if let Some(speed) = self.speed {
+            console = format!("{},{}", console, speed);
+        }
+        vec!["console=tty0".to_owned(), console]
     }
 
-    pub(crate) fn aur_helper(&self) -> &str {
-        &self.aur_helper
+    /// GRUB `serial` command, the validator makes sure of the unit.
+    pub(crate) fn grub_command(&self) -> String {
+        let mut serial = format!("serial --unit={}", self.unit().unwrap());
+
+        if let Some(speed) = self.speed {
+            serial = format!("{} --speed={}", serial, speed);
+        }
+        serial
     }
 
-    pub(crate) fn mirror_protocol(&self) -> &str {
-        &self.mirror_protocol
+    /// syslinux `SERIAL` directive.
+    pub(crate) fn syslinux_directive(&self) -> String {
+        let mut serial = format!("SERIAL {}", self.unit().unwrap());
+
+        if let Some(speed) = self.speed {
+            serial = format!("{} {}", serial, speed);
+        }
+        serial
     }
+}
 
-    pub(crate) fn multilib(&self) -> bool {
-        self.multilib
+impl System {
+    pub(crate) fn arch_host(&self) -> &str {
+        &self.arch_host
+    }
+
+    pub(crate) fn aur_helper(&self) -> &str {
+        &self.aur_helper
     }
 
     pub(crate) fn timezone(&self) -> PathBuf {
@@ -102,16 +176,639 @@ impl System {
     pub(crate) fn drive_path(&self) -> PathBuf {
         dev_path(Some(&self.drive))
     }
+
+    pub(crate) fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Fully qualified domain name, same as hostname if domain isn't set.
+    pub(crate) fn fqdn(&self) -> String {
+        if self.domain.is_empty() {
+            self.arch_host.clone()
+        } else {
+            format!("{}.{}", self.arch_host, self.domain)
+        }
+    }
+
+    pub(crate) fn nss_myhostname(&self) -> bool {
+        self.nss_myhostname
+    }
+
+    /// Resolve `.local` names with avahi and nss-mdns.
+    pub(crate) fn mdns(&self) -> bool {
+        self.mdns
+    }
+
+    pub(crate) fn hardware_clock(&self) -> HardwareClock {
+        self.hardware_clock
+    }
+
+    pub(crate) fn time_sync(&self) -> TimeSync {
+        self.time_sync
+    }
+
+    pub(crate) fn serial_console(&self) -> Option<SerialConsole<'_>> {
+        self.serial_console.as_deref().map(SerialConsole::new)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SudoPolicy {
+    #[default]
+    None,
+    Password,
+    Nopasswd,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct User {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) password: Secret,
+    /// yescrypt or SHA-512 crypt hash, see `cli mkpasswd`.
+    #[serde(default)]
+    pub(crate) password_hash: String,
+    #[serde(default)]
+    pub(crate) shell: Option<String>,
+    #[serde(default)]
+    pub(crate) groups: Vec<String>,
+    #[serde(default)]
+    pub(crate) sudo: SudoPolicy,
+    #[serde(default)]
+    pub(crate) uid: Option<u32>,
+    #[serde(default)]
+    pub(crate) authorized_keys: Vec<String>,
+}
+
+impl User {
+    pub(crate) fn home(&self) -> PathBuf {
+        let mut home = PathBuf::from("/home");
+        home.push(&self.name);
+        home
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct Root {
+    pub(crate) password: Secret,
+    pub(crate) password_hash: String,
+    pub(crate) lock: bool,
+    pub(crate) authorized_keys: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum PermitRootLogin {
+    Yes,
+    No,
+    ProhibitPassword,
+}
+
+impl PermitRootLogin {
+    /// Value of the `PermitRootLogin` sshd option.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            PermitRootLogin::Yes => "yes",
+            PermitRootLogin::No => "no",
+            PermitRootLogin::ProhibitPassword => "prohibit-password",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub(crate) struct Ssh {
+    enable: bool,
+    port: u16,
+    password_authentication: bool,
+    permit_root_login: PermitRootLogin,
+    generate_host_keys: bool,
+}
+
+impl Default for Ssh {
+    fn default() -> Self {
+        Ssh {
+            enable: false,
+            port: 22,
+            password_authentication: false,
+            permit_root_login: PermitRootLogin::No,
+            generate_host_keys: false,
+        }
+    }
+}
+
+impl Ssh {
+    pub(crate) fn enable(&self) -> bool {
+        self.enable
+    }
+
+    pub(crate) fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub(crate) fn password_authentication(&self) -> bool {
+        self.password_authentication
+    }
+
+    pub(crate) fn permit_root_login(&self) -> PermitRootLogin {
+        self.permit_root_login
+    }
+
+    /// Generate the host keys during install rather than on first boot, so
+    /// their fingerprints end up in the installer log.
+    pub(crate) fn generate_host_keys(&self) -> bool {
+        self.generate_host_keys
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum NetworkBackend {
+    #[default]
+    None,
+    Networkd,
+    NetworkManager,
+    Iwd,
+}
+
+/// Interface matched either by name or by MAC address, NetworkManager treats
+/// it as ethernet.
+#[derive(Deserialize, Debug)]
+pub(crate) struct Interface {
+    #[serde(default)]
+    pub(crate) name: Option<String>,
+    #[serde(default)]
+    pub(crate) mac: Option<String>,
+    #[serde(default)]
+    dhcp: Option<bool>,
+    /// Static addresses with prefix length, e.g. `192.168.1.10/24`.
+    #[serde(default)]
+    pub(crate) addresses: Vec<String>,
+    #[serde(default)]
+    pub(crate) gateway: Option<String>,
+    #[serde(default)]
+    pub(crate) dns: Vec<String>,
+}
+
+impl Interface {
+    /// DHCP is used unless static addresses are set.
+    pub(crate) fn dhcp(&self) -> bool {
+        self.dhcp.unwrap_or(self.addresses.is_empty())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct Wifi {
+    pub(crate) ssid: String,
+    /// Empty for open networks.
+    #[serde(default)]
+    pub(crate) psk: Secret,
+    #[serde(default)]
+    pub(crate) hidden: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct Resolved {
+    pub(crate) enable: bool,
+    pub(crate) dns: Vec<String>,
+    pub(crate) fallback_dns: Vec<String>,
+    pub(crate) domains: Vec<String>,
+    pub(crate) dnssec: Option<String>,
+    pub(crate) dns_over_tls: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct Network {
+    backend: NetworkBackend,
+    interfaces: Vec<Interface>,
+    wifi: Vec<Wifi>,
+    resolved: Resolved,
+}
+
+impl Network {
+    pub(crate) fn backend(&self) -> NetworkBackend {
+        self.backend
+    }
+
+    pub(crate) fn interfaces(&self) -> &[Interface] {
+        &self.interfaces
+    }
+
+    pub(crate) fn wifi(&self) -> &[Wifi] {
+        &self.wifi
+    }
+
+    pub(crate) fn resolved(&self) -> &Resolved {
+        &self.resolved
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LiveNetworkKind {
+    /// Runs `connection_command` if set.
+    None,
+    Ethernet,
+    Wifi,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum WifiMethod {
+    /// Writes an iwd profile, the passphrase never appears in arguments.
+    Profile,
+    /// `iwctl --passphrase`, the passphrase is visible in the process list.
+    Iwctl,
+}
+
+/// Connection of the live environment used during the installation.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub(crate) struct LiveNetwork {
+    kind: LiveNetworkKind,
+    interface: String,
+    pub(crate) ssid: String,
+    pub(crate) passphrase: Secret,
+    method: WifiMethod,
+    timeout: u64,
+    copy_profile: bool,
+}
+
+impl Default for LiveNetwork {
+    fn default() -> Self {
+        LiveNetwork {
+            kind: LiveNetworkKind::None,
+            interface: "wlan0".to_owned(),
+            ssid: String::new(),
+            passphrase: Secret::default(),
+            method: WifiMethod::Profile,
+            timeout: 60,
+            copy_profile: false,
+        }
+    }
+}
+
+impl LiveNetwork {
+    pub(crate) fn kind(&self) -> LiveNetworkKind {
+        self.kind
+    }
+
+    pub(crate) fn interface(&self) -> &str {
+        &self.interface
+    }
+
+    pub(crate) fn method(&self) -> WifiMethod {
+        self.method
+    }
+
+    /// How long to wait for connectivity, in seconds.
+    pub(crate) fn timeout(&self) -> u64 {
+        self.timeout
+    }
+
+    /// Copy the iwd profile of the Wi-Fi network to the target.
+    pub(crate) fn copy_profile(&self) -> bool {
+        self.copy_profile
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ConnectivityMethod {
+    Ping,
+    /// HEAD request, works where ICMP is blocked.
+    Http,
+    Tcp,
+    /// Assume the network is up, e.g. for offline installs.
+    None,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub(crate) struct Connectivity {
+    method: ConnectivityMethod,
+    host: String,
+    url: String,
+    address: String,
+    timeout: u64,
+}
+
+impl Default for Connectivity {
+    fn default() -> Self {
+        Connectivity {
+            method: ConnectivityMethod::Ping,
+            host: "archlinux.org".to_owned(),
+            url: "https://archlinux.org".to_owned(),
+            address: "archlinux.org:443".to_owned(),
+            timeout: 5,
+        }
+    }
+}
+
+impl Connectivity {
+    pub(crate) fn method(&self) -> ConnectivityMethod {
+        self.method
+    }
+
+    /// Host to ping.
+    pub(crate) fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// URL for the HTTP check, e.g. the mirror.
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// `host:port` for the TCP check.
+    pub(crate) fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Timeout of a single check in seconds.
+    pub(crate) fn timeout(&self) -> u64 {
+        self.timeout
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct Keyring {
+    refresh: bool,
+    package: Option<PathBuf>,
+}
+
+impl Keyring {
+    /// Upgrade archlinux-keyring from the mirrors before pacstrap.
+    pub(crate) fn refresh(&self) -> bool {
+        self.refresh
+    }
+
+    /// Local archlinux-keyring package, e.g. for offline installs.
+    pub(crate) fn package(&self) -> Option<&Path> {
+        self.package.as_deref()
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct Pacman {
+    parallel_downloads: Option<u32>,
+    color: bool,
+    ilovecandy: bool,
+    ignore_pkg: Vec<String>,
+    multilib: bool,
+}
+
+impl Pacman {
+    pub(crate) fn parallel_downloads(&self) -> Option<u32> {
+        self.parallel_downloads
+    }
+
+    pub(crate) fn color(&self) -> bool {
+        self.color
+    }
+
+    pub(crate) fn ilovecandy(&self) -> bool {
+        self.ilovecandy
+    }
+
+    pub(crate) fn ignore_pkg(&self) -> &[String] {
+        &self.ignore_pkg
+    }
+
+    pub(crate) fn multilib(&self) -> bool {
+        self.multilib
+    }
+}
+
+/// Checks the package names against the repositories before partitioning.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub(crate) struct PackageCheck {
+    enable: bool,
+    aur_rpc: String,
+}
+
+impl Default for PackageCheck {
+    fn default() -> Self {
+        PackageCheck {
+            enable: true,
+            aur_rpc: "https://aur.archlinux.org/rpc".to_owned(),
+        }
+    }
+}
+
+impl PackageCheck {
+    pub(crate) fn enable(&self) -> bool {
+        self.enable
+    }
+
+    /// Endpoint of the AUR RPC, or of a local server answering the same
+    /// `type=info` queries.
+    pub(crate) fn aur_rpc(&self) -> &str {
+        &self.aur_rpc
+    }
+}
+
+/// Custom repository, uses the mirrorlist without servers.
+#[derive(Deserialize, Debug)]
+pub(crate) struct Repository {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) sig_level: Option<String>,
+    #[serde(default)]
+    pub(crate) servers: Vec<String>,
+    /// Signing key to import, exported with `gpg --export`.
+    #[serde(default)]
+    pub(crate) key_file: Option<PathBuf>,
+    /// Fingerprint of the key, fetched from the keyserver without a file.
+    #[serde(default)]
+    pub(crate) key_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub(crate) struct Mirrors {
+    protocols: Vec<String>,
+    countries: Vec<String>,
+    count: u32,
+    sort: String,
+    /// Used as is instead of asking reflector.
+    servers: Vec<String>,
+    reflector_timer: bool,
+    /// Date of the Arch Linux Archive snapshot as `YYYY-MM-DD`, the target
+    /// gets the regular mirrors back after the install.
+    snapshot_date: Option<String>,
+    archive_server: String,
+}
+
+impl Default for Mirrors {
+    fn default() -> Self {
+        Mirrors {
+            protocols: vec![],
+            countries: vec![],
+            count: 8,
+            sort: "rate".to_owned(),
+            servers: vec![],
+            reflector_timer: false,
+            snapshot_date: None,
+            archive_server: "https://archive.archlinux.org".to_owned(),
+        }
+    }
+}
+
+impl Mirrors {
+    pub(crate) fn protocols(&self) -> &[String] {
+        &self.protocols
+    }
+
+    pub(crate) fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub(crate) fn sort(&self) -> &str {
+        &self.sort
+    }
+
+    pub(crate) fn servers(&self) -> &[String] {
+        &self.servers
+    }
+
+    /// Refresh the mirrorlist of the target weekly with the same filters.
+    pub(crate) fn reflector_timer(&self) -> bool {
+        self.reflector_timer
+    }
+
+    /// Options for reflector without `--save`, one per line of
+    /// `reflector.conf`.
+    pub(crate) fn reflector_args(&self) -> Vec<(&'static str, String)> {
+        let mut args = vec![("--protocol", self.protocols.join(","))];
+
+        if !self.countries.is_empty() {
+            args.push(("--country", self.countries.join(",")));
+        }
+        args.push(("--latest", self.count.to_string()));
+        args.push(("--sort", self.sort.clone()));
+        args
+    }
+
+    pub(crate) fn snapshot_date(&self) -> Option<&str> {
+        self.snapshot_date.as_deref()
+    }
+
+    pub(crate) fn archive_server(&self) -> &str {
+        &self.archive_server
+    }
+
+    /// Server of the snapshot, e.g.
+    /// `https://archive.archlinux.org/repos/2026/09/01/$repo/os/$arch`.
+    pub(crate) fn snapshot_server(&self) -> Option<String> {
+        let date = self.snapshot_date.as_ref()?;
+
+        Some(format!(
+            "{}/repos/{}/$repo/os/$arch",
+            self.archive_server.trim_end_matches('/'),
+            date.replace('-', "/")
+        ))
+    }
+
+    /// Contents of the mirrorlist with the snapshot or the explicit servers,
+    /// `None` if reflector picks the mirrors.
+    pub(crate) fn mirrorlist(&self) -> Option<String> {
+        if let Some(server) = self.snapshot_server() {
+            return Some(format!(
+                "# Arch Linux Archive snapshot {}\nServer = {}\n",
+                self.snapshot_date.as_ref().unwrap(),
+                server
+            ));
+        }
+        if self.servers.is_empty() {
+            return None;
+        }
+        Some(
+            self.servers
+                .iter()
+                .map(|server| format!("Server = {}\n", server))
+                .collect(),
+        )
+    }
+}
+
+/// Local package repository used instead of the mirrors.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub(crate) struct Offline {
+    repository: String,
+    name: String,
+    sig_level: String,
+}
+
+impl Default for Offline {
+    fn default() -> Self {
+        Offline {
+            repository: String::new(),
+            name: "offline".to_owned(),
+            sig_level: "Optional TrustAll".to_owned(),
+        }
+    }
+}
+
+impl Offline {
+    pub(crate) fn enabled(&self) -> bool {
+        !self.repository.is_empty()
+    }
+
+    /// Directory with the repository, if it isn't served over HTTP.
+    pub(crate) fn local_dir(&self) -> Option<PathBuf> {
+        if self.repository.starts_with('/') {
+            Some(PathBuf::from(&self.repository))
+        } else {
+            self.repository.strip_prefix("file://").map(PathBuf::from)
+        }
+    }
+
+    /// `Server` of the repository as seen from the live system.
+    pub(crate) fn server(&self) -> String {
+        match self.local_dir() {
+            Some(dir) => format!("file://{}", dir.display()),
+            None => self.repository.clone(),
+        }
+    }
+
+    /// Repository name, the database is `<name>.db`.
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// `pacman.conf` with only the offline repository.
+    pub(crate) fn pacman_conf(&self, server: &str) -> String {
+        format!(
+            "[options]\n\
+             Architecture = auto\n\
+             SigLevel = Required DatabaseOptional\n\
+             LocalFileSigLevel = Optional\n\
+             \n\
+             [{}]\n\
+             SigLevel = {}\n\
+             Server = {}\n",
+            self.name, self.sig_level, server
+        )
+    }
 }
 
 #[derive(Deserialize, Debug)]
 pub(crate) struct ShadowSocks {
-    password: String,
+    password: Secret,
     server: String,
 }
 
 impl ShadowSocks {
-    pub(crate) fn password(&self) -> &str {
+    pub(crate) fn password(&self) -> &Secret {
         &self.password
     }
 
@@ -168,6 +865,202 @@ impl Packages {
     }
 }
 
+#[derive(Deserialize, Debug)]
+pub(crate) struct GrubPassword {
+    pub(crate) user: String,
+    #[serde(default)]
+    pub(crate) password: Secret,
+    /// PBKDF2 hash produced by `grub-mkpasswd-pbkdf2`.
+    #[serde(default)]
+    pub(crate) hash: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct Bootloader {
+    removable: bool,
+    boot_first: bool,
+    remove_stale_entries: bool,
+    password: Option<GrubPassword>,
+}
+
+impl Bootloader {
+    /// Also install to the fallback path `EFI/BOOT/BOOTX64.EFI`.
+    pub(crate) fn removable(&self) -> bool {
+        self.removable
+    }
+
+    /// Move the new boot entry to the front of the boot order.
+    pub(crate) fn boot_first(&self) -> bool {
+        self.boot_first
+    }
+
+    /// Delete boot entries left with the same bootloader id.
+    pub(crate) fn remove_stale_entries(&self) -> bool {
+        self.remove_stale_entries
+    }
+
+    pub(crate) fn password(&self) -> Option<&GrubPassword> {
+        self.password.as_ref()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SwapKind {
+    None,
+    File,
+    Partition,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub(crate) struct Swap {
+    kind: SwapKind,
+    size: u64,
+    file: PathBuf,
+    pub(crate) device: String,
+    hibernate: bool,
+}
+
+impl Default for Swap {
+    fn default() -> Self {
+        Swap {
+            kind: SwapKind::None,
+            size: 0,
+            file: PathBuf::from("/swapfile"),
+            device: String::new(),
+            hibernate: false,
+        }
+    }
+}
+
+impl Swap {
+    pub(crate) fn kind(&self) -> SwapKind {
+        self.kind
+    }
+
+    /// Size of the swap in bytes, configured in MiB.
+    pub(crate) fn size(&self) -> u64 {
+        self.size * 1024 * 1024
+    }
+
+    pub(crate) fn size_mib(&self) -> u64 {
+        self.size
+    }
+
+    /// Location of the swap file inside the installed system.
+    pub(crate) fn file(&self) -> &Path {
+        &self.file
+    }
+
+    pub(crate) fn hibernate(&self) -> bool {
+        self.hibernate
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub(crate) struct Locale {
+    locales: Vec<String>,
+    lang: String,
+    overrides: BTreeMap<String, String>,
+    keymap: String,
+    font: String,
+    x11_layout: String,
+    x11_model: String,
+    x11_variant: String,
+    x11_options: String,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale {
+            locales: vec!["en_US.UTF-8".to_owned()],
+            lang: "en_US.UTF-8".to_owned(),
+            overrides: BTreeMap::new(),
+            keymap: String::new(),
+            font: String::new(),
+            x11_layout: String::new(),
+            x11_model: String::new(),
+            x11_variant: String::new(),
+            x11_options: String::new(),
+        }
+    }
+}
+
+impl Locale {
+    /// Locales to generate, as listed in `/etc/locale.gen`, e.g. `ru_RU.UTF-8`.
+    pub(crate) fn locales(&self) -> Vec<&str> {
+        self.locales.iter().map(|locale| &**locale).collect()
+    }
+
+    pub(crate) fn lang(&self) -> &str {
+        &self.lang
+    }
+
+    /// `LC_*` variables, e.g. `LC_TIME = "ru_RU.UTF-8"`.
+    pub(crate) fn overrides(&self) -> &BTreeMap<String, String> {
+        &self.overrides
+    }
+
+    pub(crate) fn keymap(&self) -> &str {
+        &self.keymap
+    }
+
+    pub(crate) fn font(&self) -> &str {
+        &self.font
+    }
+
+    pub(crate) fn x11_layout(&self) -> &str {
+        &self.x11_layout
+    }
+
+    pub(crate) fn x11_model(&self) -> &str {
+        &self.x11_model
+    }
+
+    pub(crate) fn x11_variant(&self) -> &str {
+        &self.x11_variant
+    }
+
+    pub(crate) fn x11_options(&self) -> &str {
+        &self.x11_options
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct Mkinitcpio {
+    modules: Vec<String>,
+    files: Vec<String>,
+    binaries: Vec<String>,
+    hooks: Vec<String>,
+    compression: Option<String>,
+}
+
+impl Mkinitcpio {
+    pub(crate) fn modules(&self) -> &[String] {
+        &self.modules
+    }
+
+    pub(crate) fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    pub(crate) fn binaries(&self) -> &[String] {
+        &self.binaries
+    }
+
+    pub(crate) fn hooks(&self) -> &[String] {
+        &self.hooks
+    }
+
+    pub(crate) fn compression(&self) -> Option<&str> {
+        self.compression.as_deref()
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub(crate) struct Toml {
     pub(crate) partitions: Partitions,
@@ -175,6 +1068,38 @@ pub(crate) struct Toml {
     pub(crate) shadowsocks: ShadowSocks,
     pub(crate) live_cd: LiveCD,
     pub(crate) packages: Packages,
+    #[serde(default)]
+    pub(crate) mkinitcpio: Mkinitcpio,
+    #[serde(default)]
+    pub(crate) swap: Swap,
+    #[serde(default)]
+    pub(crate) bootloader: Bootloader,
+    #[serde(default)]
+    pub(crate) locale: Locale,
+    #[serde(default)]
+    pub(crate) users: Vec<User>,
+    #[serde(default)]
+    pub(crate) root: Root,
+    #[serde(default)]
+    pub(crate) ssh: Ssh,
+    #[serde(default)]
+    pub(crate) network: Network,
+    #[serde(default)]
+    pub(crate) live_network: LiveNetwork,
+    #[serde(default)]
+    pub(crate) connectivity: Connectivity,
+    #[serde(default)]
+    pub(crate) offline: Offline,
+    #[serde(default)]
+    pub(crate) mirrors: Mirrors,
+    #[serde(default)]
+    pub(crate) keyring: Keyring,
+    #[serde(default)]
+    pub(crate) pacman: Pacman,
+    #[serde(default)]
+    pub(crate) repositories: Vec<Repository>,
+    #[serde(default)]
+    pub(crate) package_check: PackageCheck,
 }
 
 // fn map_to_conf_not_found_err(path: &mut PathBuf) -> ConfigNotFoundError {
@@ -192,7 +1117,49 @@ impl Toml {
         file.read_to_end(&mut buffer)
             .with_context(|_| errors::ConfigReadSnafu)?;
 
-        let toml: Toml = toml::from_slice(&buffer).unwrap();
+        let mut toml: Toml = toml::from_slice(&buffer).unwrap();
+
+        // The buffer holds every secret written in plaintext.
+        zeroize_bytes(&mut buffer);
+
+        toml.add_legacy_user();
+        toml.apply_legacy_mirror_protocol();
+        toml.pacman.multilib |= toml.system.multilib;
         Ok(toml)
     }
+
+    /// Turns `arch_username` and `arch_userpass` of older configs into the
+    /// only entry of `users`.
+    fn add_legacy_user(&mut self) {
+        if !self.users.is_empty() || self.system.arch_username.is_empty() {
+            return;
+        }
+        let sudo = if self.packages.pacman_system.iter().any(|pkg| pkg == "sudo") {
+            SudoPolicy::Password
+        } else {
+            SudoPolicy::None
+        };
+        self.users.push(User {
+            name: self.system.arch_username.clone(),
+            password: mem::take(&mut self.system.arch_userpass),
+            password_hash: String::new(),
+            shell: None,
+            groups: vec![],
+            sudo,
+            uid: None,
+            authorized_keys: vec![],
+        });
+    }
+
+    /// Takes the protocols from `mirror_protocol` of older configs.
+    fn apply_legacy_mirror_protocol(&mut self) {
+        if !self.mirrors.protocols.is_empty() {
+            return;
+        }
+        self.mirrors.protocols = match self.system.mirror_protocol.as_str() {
+            "" => vec!["https".to_owned()],
+            "all" => vec!["https".to_owned(), "http".to_owned()],
+            protocol => vec![protocol.to_owned()],
+        };
+    }
 }
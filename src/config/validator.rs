@@ -1,8 +1,70 @@
 use snafu::ensure;
 
-use crate::errors;
+use crate::{
+    errors,
+    utils::{block_device_size, mem_total, passwd::is_password_hash},
+};
 
-use {super::toml, crate::errors::ALIResult, std::rc::Rc};
+use {
+    super::toml::{
+        self, ConnectivityMethod, LiveNetworkKind, NetworkBackend, PermitRootLogin, SudoPolicy,
+        SwapKind,
+    },
+    crate::errors::ALIResult,
+    std::{fs, net::IpAddr, path::PathBuf, rc::Rc},
+};
+
+/// Checks `address/prefix`, e.g. `192.168.1.10/24` or `fd00::10/64`.
+fn is_address_with_prefix(value: &str) -> bool {
+    let (address, prefix) = match value.split_once('/') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let max_prefix = match address.parse() {
+        Ok(IpAddr::V4(_)) => 32,
+        Ok(IpAddr::V6(_)) => 128,
+        Err(_) => return false,
+    };
+    matches!(prefix.parse::<u8>(), Ok(prefix) if prefix <= max_prefix)
+}
+
+/// Checks `YYYY-MM-DD`, the archive starts in 2013.
+fn is_date(value: &str) -> bool {
+    let parts: Vec<_> = value.split('-').collect();
+
+    if parts.len() != 3
+        || parts
+            .iter()
+            .any(|part| !part.chars().all(|c| c.is_ascii_digit()))
+    {
+        return false;
+    }
+    let (year, month, day) = match (parts[0].len(), parts[1].len(), parts[2].len()) {
+        (4, 2, 2) => (
+            parts[0].parse::<u32>().unwrap(),
+            parts[1].parse::<u32>().unwrap(),
+            parts[2].parse::<u32>().unwrap(),
+        ),
+        _ => return false,
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    year >= 2013 && day >= 1 && day <= days
+}
+
+fn is_mac_address(value: &str) -> bool {
+    let octets: Vec<_> = value.split(':').collect();
+    octets.len() == 6
+        && octets
+            .iter()
+            .all(|octet| octet.len() == 2 && octet.chars().all(|c| c.is_ascii_hexdigit()))
+}
 
 pub(crate) struct ConfigValidator {
     toml: Rc<toml::Toml>,
@@ -16,6 +78,28 @@ impl ConfigValidator {
     pub(crate) fn validate(&self) -> ALIResult<()> {
         self.validate_drive()?;
         self.validate_system()?;
+        self.validate_swap()?;
+        self.validate_bootloader()?;
+        self.validate_locale()?;
+        self.validate_users()?;
+        self.validate_ssh()?;
+        self.validate_network()?;
+        self.validate_live_network()?;
+        self.validate_connectivity()?;
+        self.validate_offline()?;
+        self.validate_mirrors()?;
+        self.validate_repositories()?;
+        self.validate_keyring()?;
+        self.validate_package_check()?;
+        Ok(())
+    }
+
+    /// Checks files which only exist on the live system, `chroot-install`
+    /// validates the config again inside the target.
+    pub(crate) fn validate_live(&self) -> ALIResult<()> {
+        self.validate_offline_files()?;
+        self.validate_repository_files()?;
+        self.validate_keyring_files()?;
         Ok(())
     }
 
@@ -28,7 +112,7 @@ impl ConfigValidator {
         );
         if self.toml.partitions.root.encryption {
             ensure!(
-                !self.toml.partitions.root.password.is_empty(),
+                self.toml.partitions.root.password.is_set(),
                 errors::ConfigInvalidSnafu {
                     desc: "drive password not set".to_owned(),
                 }
@@ -45,15 +129,16 @@ impl ConfigValidator {
             }
         );
         ensure!(
-            !self.toml.system.arch_username().is_empty(),
+            !self.toml.system.arch_host().contains('.'),
             errors::ConfigInvalidSnafu {
-                desc: "username not set".to_owned(),
+                desc: "hostname must not contain dots, set domain instead".to_owned(),
             }
         );
         ensure!(
-            !self.toml.system.arch_userpass().is_empty(),
+            !self.toml.system.domain().starts_with('.')
+                && !self.toml.system.domain().ends_with('.'),
             errors::ConfigInvalidSnafu {
-                desc: "user password not set".to_owned(),
+                desc: "domain must not start or end with a dot".to_owned(),
             }
         );
         ensure!(
@@ -62,6 +147,741 @@ impl ConfigValidator {
                 desc: "timezone not set".to_owned(),
             }
         );
+        ensure!(
+            self.toml.system.timezone().is_file(),
+            errors::ConfigInvalidSnafu {
+                desc: format!("timezone {:?} not found", self.toml.system.timezone()),
+            }
+        );
+        if let Some(console) = self.toml.system.serial_console() {
+            ensure!(
+                console.unit().is_some(),
+                errors::ConfigInvalidSnafu {
+                    desc: format!(
+                        "serial console must be a ttyS device, got {}",
+                        console.device
+                    ),
+                }
+            );
+            ensure!(
+                !matches!(console.speed, Some(speed) if speed.parse::<u32>().is_err()),
+                errors::ConfigInvalidSnafu {
+                    desc: "serial console speed must be a number".to_owned(),
+                }
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_swap(&self) -> ALIResult<()> {
+        let swap = &self.toml.swap;
+
+        if swap.kind() == SwapKind::File || swap.device.is_empty() {
+            ensure!(
+                swap.kind() == SwapKind::None || swap.size() > 0,
+                errors::ConfigInvalidSnafu {
+                    desc: "swap size not set".to_owned(),
+                }
+            );
+        }
+        if !swap.hibernate() {
+            return Ok(());
+        }
+        ensure!(
+            swap.kind() != SwapKind::None,
+            errors::ConfigInvalidSnafu {
+                desc: "hibernation requires swap".to_owned(),
+            }
+        );
+        ensure!(
+            swap.kind() != SwapKind::Partition || !self.toml.partitions.root.encryption,
+            errors::ConfigInvalidSnafu {
+                desc: "encrypted root can't hibernate to a swap partition, use a swap file"
+                    .to_owned(),
+            }
+        );
+        // resume_offset is computed inside the chroot.
+        if swap.kind() == SwapKind::File {
+            let package = if self.toml.partitions.root.fs == "btrfs" {
+                "btrfs-progs"
+            } else {
+                "e2fsprogs"
+            };
+            ensure!(
+                self.toml.packages.pacman_system().contains(&package),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("hibernation to a swap file requires {} package", package),
+                }
+            );
+        }
+        let size = if swap.kind() == SwapKind::Partition && !swap.device.is_empty() {
+            let mut device = PathBuf::from("/dev");
+            device.push(&swap.device);
+            block_device_size(device).unwrap_or(0)
+        } else {
+            swap.size()
+        };
+        ensure!(
+            size >= mem_total(),
+            errors::ConfigInvalidSnafu {
+                desc: "swap must be at least as large as RAM for hibernation".to_owned(),
+            }
+        );
+        Ok(())
+    }
+
+    pub(crate) fn validate_bootloader(&self) -> ALIResult<()> {
+        let password = match self.toml.bootloader.password() {
+            Some(password) => password,
+            None => return Ok(()),
+        };
+        ensure!(
+            !password.user.is_empty(),
+            errors::ConfigInvalidSnafu {
+                desc: "bootloader superuser not set".to_owned(),
+            }
+        );
+        ensure!(
+            password.password.is_set() || !password.hash.is_empty(),
+            errors::ConfigInvalidSnafu {
+                desc: "bootloader password not set".to_owned(),
+            }
+        );
+        ensure!(
+            password.hash.is_empty() || password.hash.starts_with("grub.pbkdf2."),
+            errors::ConfigInvalidSnafu {
+                desc: "bootloader password hash must be generated by grub-mkpasswd-pbkdf2"
+                    .to_owned(),
+            }
+        );
+        Ok(())
+    }
+
+    pub(crate) fn validate_locale(&self) -> ALIResult<()> {
+        let locale = &self.toml.locale;
+        let locale_name = |locale: &str| locale.split_whitespace().next().unwrap_or("").to_owned();
+        let names: Vec<String> = locale.locales().into_iter().map(locale_name).collect();
+
+        ensure!(
+            !names.is_empty(),
+            errors::ConfigInvalidSnafu {
+                desc: "no locales to generate".to_owned(),
+            }
+        );
+        // Lists every locale which may be enabled in /etc/locale.gen.
+        if let Ok(supported) = fs::read_to_string("/usr/share/i18n/SUPPORTED") {
+            let supported: Vec<String> = supported.lines().map(locale_name).collect();
+
+            for name in &names {
+                ensure!(
+                    supported.contains(name),
+                    errors::ConfigInvalidSnafu {
+                        desc: format!("unknown locale {}", name),
+                    }
+                );
+            }
+        }
+        let mut used = vec![locale.lang()];
+        used.extend(locale.overrides().values().map(|value| &**value));
+
+        for value in used {
+            ensure!(
+                names.iter().any(|name| name == value),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("locale {} is not generated", value),
+                }
+            );
+        }
+        for key in locale.overrides().keys() {
+            ensure!(
+                key.starts_with("LC_") && key != "LC_ALL",
+                errors::ConfigInvalidSnafu {
+                    desc: format!("unexpected locale variable {}", key),
+                }
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_users(&self) -> ALIResult<()> {
+        let users = &self.toml.users;
+
+        ensure!(
+            !users.is_empty(),
+            errors::ConfigInvalidSnafu {
+                desc: "username not set".to_owned(),
+            }
+        );
+        for (index, user) in users.iter().enumerate() {
+            ensure!(
+                !user.name.is_empty() && user.name != "root",
+                errors::ConfigInvalidSnafu {
+                    desc: format!("invalid username {:?}", user.name),
+                }
+            );
+            ensure!(
+                users[..index].iter().all(|other| other.name != user.name),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("user {} is listed twice", user.name),
+                }
+            );
+            ensure!(
+                user.password.is_set() == user.password_hash.is_empty(),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("either password or hash of user {} must be set", user.name),
+                }
+            );
+            ensure!(
+                user.password_hash.is_empty() || is_password_hash(&user.password_hash),
+                errors::ConfigInvalidSnafu {
+                    desc: format!(
+                        "password hash of user {} must be yescrypt or SHA-512",
+                        user.name
+                    ),
+                }
+            );
+            ensure!(
+                user.sudo == SudoPolicy::None
+                    || self.toml.packages.pacman_system().contains(&"sudo"),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("sudo rights of user {} require sudo package", user.name),
+                }
+            );
+        }
+        let root = &self.toml.root;
+
+        ensure!(
+            !root.password.is_set() || root.password_hash.is_empty(),
+            errors::ConfigInvalidSnafu {
+                desc: "root password and password hash are mutually exclusive".to_owned(),
+            }
+        );
+        ensure!(
+            root.password_hash.is_empty() || is_password_hash(&root.password_hash),
+            errors::ConfigInvalidSnafu {
+                desc: "root password hash must be yescrypt or SHA-512".to_owned(),
+            }
+        );
+        ensure!(
+            !(root.lock && (root.password.is_set() || !root.password_hash.is_empty())),
+            errors::ConfigInvalidSnafu {
+                desc: "root account can't be locked and have a password".to_owned(),
+            }
+        );
+        Ok(())
+    }
+
+    pub(crate) fn validate_ssh(&self) -> ALIResult<()> {
+        let ssh = &self.toml.ssh;
+
+        if !ssh.enable() {
+            return Ok(());
+        }
+        ensure!(
+            self.toml.packages.pacman_system().contains(&"openssh"),
+            errors::ConfigInvalidSnafu {
+                desc: "ssh requires openssh package".to_owned(),
+            }
+        );
+        ensure!(
+            ssh.port() != 0,
+            errors::ConfigInvalidSnafu {
+                desc: "ssh port must not be 0".to_owned(),
+            }
+        );
+        let root = &self.toml.root;
+        let root_keys =
+            ssh.permit_root_login() != PermitRootLogin::No && !root.authorized_keys.is_empty();
+
+        // Without passwords only keys let anybody in.
+        ensure!(
+            ssh.password_authentication()
+                || root_keys
+                || self
+                    .toml
+                    .users
+                    .iter()
+                    .any(|user| !user.authorized_keys.is_empty()),
+            errors::ConfigInvalidSnafu {
+                desc: "ssh password authentication is disabled, but no authorized keys are set"
+                    .to_owned(),
+            }
+        );
+        ensure!(
+            ssh.permit_root_login() != PermitRootLogin::ProhibitPassword
+                || !root.authorized_keys.is_empty(),
+            errors::ConfigInvalidSnafu {
+                desc: "ssh root login with a key requires root authorized keys".to_owned(),
+            }
+        );
+        Ok(())
+    }
+
+    pub(crate) fn validate_network(&self) -> ALIResult<()> {
+        let network = &self.toml.network;
+        let packages = self.toml.packages.pacman_system();
+        let backend = network.backend();
+
+        let package = match backend {
+            NetworkBackend::None => None,
+            NetworkBackend::Networkd => None,
+            NetworkBackend::NetworkManager => Some("networkmanager"),
+            NetworkBackend::Iwd => Some("iwd"),
+        };
+        if let Some(package) = package {
+            ensure!(
+                packages.contains(&package),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("network backend requires {} package", package),
+                }
+            );
+        }
+        ensure!(
+            backend != NetworkBackend::None
+                || (network.interfaces().is_empty() && network.wifi().is_empty()),
+            errors::ConfigInvalidSnafu {
+                desc: "network interfaces and wifi require a network backend".to_owned(),
+            }
+        );
+        // networkd doesn't associate with access points by itself.
+        ensure!(
+            backend != NetworkBackend::Networkd
+                || network.wifi().is_empty()
+                || packages.contains(&"iwd"),
+            errors::ConfigInvalidSnafu {
+                desc: "wifi with networkd requires iwd package".to_owned(),
+            }
+        );
+        ensure!(
+            backend != NetworkBackend::Iwd || network.interfaces().is_empty(),
+            errors::ConfigInvalidSnafu {
+                desc: "iwd manages only wifi, use networkd for interfaces".to_owned(),
+            }
+        );
+        for (index, interface) in network.interfaces().iter().enumerate() {
+            let desc = |what: &str| format!("network interface {}: {}", index, what);
+
+            ensure!(
+                interface.name.is_some() != interface.mac.is_some(),
+                errors::ConfigInvalidSnafu {
+                    desc: desc("either name or mac must be set"),
+                }
+            );
+            if let Some(ref mac) = interface.mac {
+                ensure!(
+                    is_mac_address(mac),
+                    errors::ConfigInvalidSnafu {
+                        desc: desc(&format!("invalid MAC address {:?}", mac)),
+                    }
+                );
+            }
+            ensure!(
+                interface.dhcp() || !interface.addresses.is_empty(),
+                errors::ConfigInvalidSnafu {
+                    desc: desc("neither dhcp nor addresses are set"),
+                }
+            );
+            for address in &interface.addresses {
+                ensure!(
+                    is_address_with_prefix(address),
+                    errors::ConfigInvalidSnafu {
+                        desc: desc(&format!("invalid address {:?}", address)),
+                    }
+                );
+            }
+            for ip in interface.gateway.iter().chain(&interface.dns) {
+                ensure!(
+                    ip.parse::<IpAddr>().is_ok(),
+                    errors::ConfigInvalidSnafu {
+                        desc: desc(&format!("invalid IP address {:?}", ip)),
+                    }
+                );
+            }
+        }
+        for wifi in network.wifi() {
+            ensure!(
+                !wifi.ssid.is_empty() && wifi.ssid.len() <= 32,
+                errors::ConfigInvalidSnafu {
+                    desc: format!("invalid wifi SSID {:?}", wifi.ssid),
+                }
+            );
+        }
+        let resolved = network.resolved();
+
+        for ip in resolved.dns.iter().chain(&resolved.fallback_dns) {
+            // `address#server name` is accepted for DNS over TLS.
+            let address = ip.split('#').next().unwrap();
+
+            ensure!(
+                address.parse::<IpAddr>().is_ok(),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("invalid DNS server {:?}", ip),
+                }
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_live_network(&self) -> ALIResult<()> {
+        let live_network = &self.toml.live_network;
+
+        if live_network.kind() != LiveNetworkKind::Wifi {
+            return Ok(());
+        }
+        ensure!(
+            !live_network.ssid.is_empty() && live_network.ssid.len() <= 32,
+            errors::ConfigInvalidSnafu {
+                desc: format!("invalid live network SSID {:?}", live_network.ssid),
+            }
+        );
+        ensure!(
+            !live_network.interface().is_empty(),
+            errors::ConfigInvalidSnafu {
+                desc: "live network interface not set".to_owned(),
+            }
+        );
+        ensure!(
+            live_network.timeout() > 0,
+            errors::ConfigInvalidSnafu {
+                desc: "live network timeout must be positive".to_owned(),
+            }
+        );
+        Ok(())
+    }
+
+    pub(crate) fn validate_connectivity(&self) -> ALIResult<()> {
+        let connectivity = &self.toml.connectivity;
+
+        match connectivity.method() {
+            ConnectivityMethod::Ping => ensure!(
+                !connectivity.host().is_empty(),
+                errors::ConfigInvalidSnafu {
+                    desc: "connectivity host not set".to_owned(),
+                }
+            ),
+            ConnectivityMethod::Http => ensure!(
+                connectivity.url().starts_with("http://")
+                    || connectivity.url().starts_with("https://"),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("invalid connectivity URL {:?}", connectivity.url()),
+                }
+            ),
+            ConnectivityMethod::Tcp => ensure!(
+                matches!(
+                    connectivity.address().rsplit_once(':'),
+                    Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok()
+                ),
+                errors::ConfigInvalidSnafu {
+                    desc: format!(
+                        "connectivity address {:?} must be host:port",
+                        connectivity.address()
+                    ),
+                }
+            ),
+            ConnectivityMethod::None => (),
+        }
+        ensure!(
+            connectivity.timeout() > 0,
+            errors::ConfigInvalidSnafu {
+                desc: "connectivity timeout must be positive".to_owned(),
+            }
+        );
+        Ok(())
+    }
+
+    pub(crate) fn validate_offline(&self) -> ALIResult<()> {
+        let offline = &self.toml.offline;
+
+        if !offline.enabled() {
+            return Ok(());
+        }
+        ensure!(
+            !offline.name().is_empty() && !offline.name().contains(&['[', ']', '/'][..]),
+            errors::ConfigInvalidSnafu {
+                desc: format!("invalid offline repository name {:?}", offline.name()),
+            }
+        );
+        if offline.local_dir().is_none() {
+            let server = offline.server();
+
+            ensure!(
+                server.starts_with("http://") || server.starts_with("https://"),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("invalid offline repository {:?}", server),
+                }
+            );
+        }
+        Ok(())
+    }
+
+    /// The target sees the local repository at `OFFLINE_REPO_DIR` instead.
+    pub(crate) fn validate_offline_files(&self) -> ALIResult<()> {
+        let offline = &self.toml.offline;
+
+        if let Some(mut db) = offline.local_dir() {
+            db.push(format!("{}.db", offline.name()));
+
+            ensure!(
+                db.exists(),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("offline repository database {:?} not found", db),
+                }
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_mirrors(&self) -> ALIResult<()> {
+        let mirrors = &self.toml.mirrors;
+
+        for protocol in mirrors.protocols() {
+            ensure!(
+                ["https", "http", "rsync", "ftp"].contains(&protocol.as_str()),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("unknown mirror protocol {:?}", protocol),
+                }
+            );
+        }
+        ensure!(
+            ["age", "rate", "country", "score", "delay"].contains(&mirrors.sort()),
+            errors::ConfigInvalidSnafu {
+                desc: format!("unknown mirror sort order {:?}", mirrors.sort()),
+            }
+        );
+        ensure!(
+            mirrors.count() > 0,
+            errors::ConfigInvalidSnafu {
+                desc: "mirror count must be positive".to_owned(),
+            }
+        );
+        for server in mirrors.servers() {
+            ensure!(
+                server.contains("$repo"),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("mirror server {:?} doesn't contain $repo", server),
+                }
+            );
+        }
+        if let Some(date) = mirrors.snapshot_date() {
+            ensure!(
+                is_date(date),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("snapshot date {:?} must be YYYY-MM-DD", date),
+                }
+            );
+            ensure!(
+                mirrors.servers().is_empty() && !mirrors.reflector_timer(),
+                errors::ConfigInvalidSnafu {
+                    desc: "snapshot date excludes mirror servers and reflector timer".to_owned(),
+                }
+            );
+            ensure!(
+                !self.toml.offline.enabled(),
+                errors::ConfigInvalidSnafu {
+                    desc: "snapshot date excludes offline repository".to_owned(),
+                }
+            );
+            ensure!(
+                ["http://", "https://", "file://"]
+                    .iter()
+                    .any(|scheme| mirrors.archive_server().starts_with(scheme)),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("invalid archive server {:?}", mirrors.archive_server()),
+                }
+            );
+        }
+        if mirrors.reflector_timer() {
+            ensure!(
+                mirrors.servers().is_empty(),
+                errors::ConfigInvalidSnafu {
+                    desc: "reflector timer would replace the explicit mirror servers".to_owned(),
+                }
+            );
+            ensure!(
+                self.toml.packages.pacman_system().contains(&"reflector"),
+                errors::ConfigInvalidSnafu {
+                    desc: "reflector timer requires reflector package".to_owned(),
+                }
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_repositories(&self) -> ALIResult<()> {
+        let repositories = &self.toml.repositories;
+
+        for (index, repository) in repositories.iter().enumerate() {
+            ensure!(
+                !repository.name.is_empty()
+                    && !repository.name.contains(&['[', ']', '/', ' '][..])
+                    && repository.name != "options",
+                errors::ConfigInvalidSnafu {
+                    desc: format!("invalid repository name {:?}", repository.name),
+                }
+            );
+            ensure!(
+                repositories[..index]
+                    .iter()
+                    .all(|other| other.name != repository.name),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("repository {} is listed twice", repository.name),
+                }
+            );
+            if let Some(ref key_id) = repository.key_id {
+                ensure!(
+                    key_id.len() >= 16 && key_id.chars().all(|c| c.is_ascii_hexdigit()),
+                    errors::ConfigInvalidSnafu {
+                        desc: format!(
+                            "key id {:?} of repository {} must be a fingerprint",
+                            key_id, repository.name
+                        ),
+                    }
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Key files are imported before the chroot.
+    pub(crate) fn validate_repository_files(&self) -> ALIResult<()> {
+        for repository in &self.toml.repositories {
+            if let Some(ref key_file) = repository.key_file {
+                ensure!(
+                    key_file.exists(),
+                    errors::ConfigInvalidSnafu {
+                        desc: format!(
+                            "key file {:?} of repository {} not found",
+                            key_file, repository.name
+                        ),
+                    }
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_keyring(&self) -> ALIResult<()> {
+        let keyring = &self.toml.keyring;
+
+        ensure!(
+            !(keyring.refresh() && keyring.package().is_some()),
+            errors::ConfigInvalidSnafu {
+                desc: "keyring refresh and package are exclusive".to_owned(),
+            }
+        );
+        ensure!(
+            !(keyring.refresh() && self.toml.offline.enabled()),
+            errors::ConfigInvalidSnafu {
+                desc: "keyring can't be refreshed offline, set keyring package instead".to_owned(),
+            }
+        );
+        Ok(())
+    }
+
+    /// The keyring package is installed on the live system.
+    pub(crate) fn validate_keyring_files(&self) -> ALIResult<()> {
+        if let Some(package) = self.toml.keyring.package() {
+            ensure!(
+                package.exists(),
+                errors::ConfigInvalidSnafu {
+                    desc: format!("keyring package {:?} not found", package),
+                }
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_package_check(&self) -> ALIResult<()> {
+        let check = &self.toml.package_check;
+
+        if !check.enable() {
+            return Ok(());
+        }
+        ensure!(
+            check.aur_rpc().starts_with("http://") || check.aur_rpc().starts_with("https://"),
+            errors::ConfigInvalidSnafu {
+                desc: format!("invalid AUR RPC URL {:?}", check.aur_rpc()),
+            }
+        );
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{env, fs},
+    };
+
+    const CONFIG: &str = r#"
+        [partitions.efi]
+        device = "sda1"
+        bootloader_id = "arch"
+
+        [partitions.boot]
+        device = "sda2"
+
+        [partitions.root]
+        device = "sda3"
+        crypt_mapping = "root"
+        password = ""
+        encryption = false
+        fs = "ext4"
+
+        [system]
+        drive = "sda"
+        arch_host = "arch"
+        aur_helper = ""
+        timezone = "UTC"
+
+        [shadowsocks]
+        password = ""
+        server = ""
+
+        [live_cd]
+        profile = "releng"
+        installer_location = "installer"
+
+        [packages]
+        archiso = []
+        aur = []
+        pacman = []
+        pacman_system = ["base"]
+        vscode = []
+
+        [[users]]
+        name = "user"
+        password = "password"
+    "#;
+
+    fn validator(offline: &str) -> ConfigValidator {
+        let buffer = format!("{}\n[offline]\nrepository = {:?}\n", CONFIG, offline);
+        let toml: toml::Toml = ::toml::from_str(&buffer).unwrap();
+        ConfigValidator::new(Rc::new(toml))
+    }
+
+    #[test]
+    fn local_repository_is_checked_on_the_live_system() {
+        let validator = validator("/nonexistent/installer-repo");
+
+        // What chroot-install checks inside the target.
+        assert!(validator.validate_offline().is_ok());
+        assert!(validator.validate_live().is_err());
+    }
+
+    #[test]
+    fn local_repository_passes_both_validations() {
+        let dir = env::temp_dir().join(format!("installer-repo-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("offline.db"), "").unwrap();
+
+        let validator = validator(dir.to_str().unwrap());
+        let live = validator.validate_live();
+        let chroot = validator.validate_offline();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(live.is_ok());
+        assert!(chroot.is_ok());
+    }
+}
@@ -9,3 +9,18 @@ pub(crate) const LINUX_GUID: [u8; 16] = [
 pub(crate) const BIOS_BOOT_GUID: [u8; 16] = [
     0x48, 0x61, 0x68, 0x21, 0x49, 0x64, 0x6f, 0x6e, 0x74, 0x4e, 0x65, 0x65, 0x64, 0x45, 0x46, 0x49,
 ];
+
+pub(crate) const SWAP_GUID: [u8; 16] = [
+    0x6D, 0xFD, 0x57, 0x06, 0xAB, 0xA4, 0xC4, 0x43, 0x84, 0xE5, 0x09, 0x33, 0xC8, 0x4B, 0x4F, 0x4F,
+];
+
+/// pacman.conf with the offline repository, next to the installer binary in
+/// the target.
+pub(crate) const OFFLINE_PACMAN_CONF: &str = "pacman.offline.conf";
+
+/// Where a local offline repository is mounted in the target.
+pub(crate) const OFFLINE_REPO_DIR: &str = "/var/cache/installer-repo";
+
+/// Where `file:` secrets used inside the chroot are copied in the target, the
+/// paths of the live system don't exist there.
+pub(crate) const SECRETS_DIR: &str = "/root/installer/secrets";
@@ -28,6 +28,19 @@ pub enum Error {
     #[snafu(display("Error: network access required"))]
     Network,
 
+    #[snafu(display("Error: no network connection after {} seconds: {}", timeout, source))]
+    NetworkTimeout {
+        timeout: u64,
+        #[snafu(source(from(Error, Box::new)))]
+        source: Box<Error>,
+    },
+
+    #[snafu(display("Error: unable to resolve {}", host))]
+    DnsResolution { host: String },
+
+    #[snafu(display("Error: {} is unreachable", target))]
+    NetworkUnreachable { target: String },
+
     #[snafu(display("Error: command must be executed from super user"))]
     SudoRequired,
 
@@ -38,6 +51,92 @@ pub enum Error {
     #[snafu(display("Device {:?} not found", device))]
     DeviceNotFound { device: PathBuf },
 
+    #[snafu(display("Unable to set timezone {:?}", timezone))]
+    Timezone {
+        timezone: PathBuf,
+        source: io::Error,
+    },
+
+    #[snafu(display("Environment variable {} with a secret is not set", var))]
+    SecretEnv { var: String },
+
+    #[snafu(display("Unable to read a secret from {:?}", path))]
+    SecretFile { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Secret {} was already used and cleared", name))]
+    SecretCleared { name: String },
+
+    #[snafu(display("Passwords do not match"))]
+    PasswordMismatch,
+
+    #[snafu(display("Password is empty"))]
+    PasswordEmpty,
+
+    #[snafu(display("Unable to write sudo rules to {:?}", path))]
+    Sudoers { path: PathBuf, source: io::Error },
+
+    #[snafu(display("visudo rejected sudo rules in {:?}", path))]
+    SudoersInvalid { path: PathBuf },
+
+    #[snafu(display("Unable to read /etc/passwd"))]
+    PasswdRead { source: io::Error },
+
+    #[snafu(display("User {} not found", user))]
+    UserNotFound { user: String },
+
+    #[snafu(display("Unable to run {:?} as {}", program, user))]
+    RunAsSpawn {
+        user: String,
+        program: String,
+        source: io::Error,
+    },
+
+    #[snafu(display("{:?} run as {} failed with code {:?}", program, user, code))]
+    RunAsFailed {
+        user: String,
+        program: String,
+        code: Option<i32>,
+    },
+
+    #[snafu(display("Unable to install {}", failed.join(", ")))]
+    InstallIncomplete { failed: Vec<String> },
+
+    #[snafu(display("Unable to find the physical offset of swap file {:?}", file))]
+    SwapFileOffset { file: PathBuf },
+
+    #[snafu(display("No boot entry labelled {:?} after grub-install", label))]
+    BootEntryNotFound { label: String },
+
+    #[snafu(display("Unable to write GRUB configuration {:?}", path))]
+    GrubConfig { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Unable to make the entries of {:?} bootable without password", path))]
+    GrubUnrestricted { path: PathBuf },
+
+    #[snafu(display("Unable to write sshd configuration {:?}", path))]
+    SshConfig { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Unable to write network configuration {:?}", path))]
+    NetworkConfig { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Packages not found: {}", packages.join(", ")))]
+    PackageNotFound { packages: Vec<String> },
+
+    #[snafu(display("Unknown packages: {}", packages.join(", ")))]
+    UnknownPackages { packages: Vec<String> },
+
+    #[snafu(display("Files already exist on the target: {}", files.join(", ")))]
+    PackageFileConflict { files: Vec<String> },
+
+    #[snafu(display("{} failed after {} attempts", command, attempts))]
+    PacmanFailed { command: String, attempts: u32 },
+
+    #[snafu(display("Invalid response of AUR RPC {}: {}", rpc, source))]
+    AurResponse {
+        rpc: String,
+        source: serde_json::Error,
+    },
+
     #[snafu(display("Command {:?} failed", message))]
     CommandExecution { message: String },
 }
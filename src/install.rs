@@ -1,19 +1,32 @@
-use snafu::ensure;
+use snafu::{ensure, ResultExt};
 
 use crate::{
-    config::{Config, InstallStep, InstallStepRange},
-    constants::{EFI_GUID, LINUX_GUID},
+    config::{
+        staged_path, zeroize, Config, InstallStep, InstallStepRange, LiveNetworkKind, SwapKind,
+        WifiMethod,
+    },
+    constants::{
+        EFI_GUID, LINUX_GUID, OFFLINE_PACMAN_CONF, OFFLINE_REPO_DIR, SECRETS_DIR, SWAP_GUID,
+    },
     errors::{self, ALIResult},
-    utils::{answer, command::Command, exe_dir, partitions::Partitions, Mounted},
+    utils::{
+        answer, command::Command, connectivity, exe_dir, iwd, package_check, pacman,
+        pacman_conf::PacmanConf, pacman_key, partitions::Partitions, passwd::read_new_password,
+        sudoers::TemporaryNopasswd, Mounted,
+    },
 };
 
 use {
     gptman::GPT,
     std::{
-        fs::{self, create_dir, File},
+        env,
+        fs::{self, create_dir},
         io::{prelude::*, stdin},
+        os::unix::fs::{symlink, DirBuilderExt, OpenOptionsExt, PermissionsExt},
         path::{Path, PathBuf},
         process::Stdio,
+        thread::sleep,
+        time::{Duration, Instant},
     },
 };
 
@@ -74,6 +87,21 @@ fn add_boot_partition(gpt: &mut GPT, part_index: u32) {
     };
 }
 
+fn add_swap_partition(gpt: &mut GPT, part_index: u32, size: u64) {
+    let partition_size = size / gpt.sector_size;
+    let starting_lba = gpt[part_index - 1].ending_lba + 1;
+    let ending_lba = starting_lba + partition_size - 1;
+
+    gpt[part_index] = gptman::GPTPartitionEntry {
+        partition_type_guid: SWAP_GUID,
+        unique_partition_guid: *uuid::Uuid::new_v4().as_bytes(),
+        starting_lba,
+        ending_lba,
+        attribute_bits: 0,
+        partition_name: "Arch Linux swap partition".into(),
+    };
+}
+
 fn add_root_partition(gpt: &mut GPT, part_index: u32) {
     let starting_lba = gpt[part_index - 1].ending_lba + 1;
     let ending_lba = gpt.header.last_usable_lba;
@@ -107,6 +135,7 @@ impl<'a, 'b> Installer<'a, 'b> {
         let partitions = Partitions::new(
             config.system().drive_path(),
             &config.partitions().root.device,
+            &config.swap().device,
             &config.partitions().boot.device,
             &config.partitions().efi.device,
         );
@@ -133,12 +162,9 @@ impl<'a, 'b> Installer<'a, 'b> {
             .spawn()
             .unwrap();
 
-        cryptsetup
-            .stdin
-            .as_mut()
-            .unwrap()
-            .write_all(password.as_bytes())
-            .unwrap();
+        let stdin = cryptsetup.stdin.as_mut().unwrap();
+        stdin.write_all(password.as_bytes()).unwrap();
+        stdin.write_all(b"\n").unwrap();
 
         let status = cryptsetup.wait().unwrap();
         assert!(status.success());
@@ -153,12 +179,9 @@ impl<'a, 'b> Installer<'a, 'b> {
             .spawn()
             .unwrap();
 
-        cryptsetup
-            .stdin
-            .as_mut()
-            .unwrap()
-            .write_all(password.as_bytes())
-            .unwrap();
+        let stdin = cryptsetup.stdin.as_mut().unwrap();
+        stdin.write_all(password.as_bytes()).unwrap();
+        stdin.write_all(b"\n").unwrap();
 
         let status = cryptsetup.wait().unwrap();
         assert!(status.success());
@@ -248,44 +271,54 @@ impl<'a, 'b> Installer<'a, 'b> {
         self
     }
 
-    pub(crate) fn network_available(&mut self) -> ALIResult<bool> {
-        let status = Command::new("ping")
-            .args(&["-c", "1", "archlinux.org"])
-            .stdout(Stdio::null())
+    pub(crate) fn network_available(&self) -> ALIResult<bool> {
+        match connectivity::check(self.config.connectivity()) {
+            Ok(()) => Ok(true),
+            Err(error) => {
+                println!("{}", error);
+                Ok(false)
+            }
+        }
+    }
+
+    fn wait_for_network(&self, deadline: Instant) -> ALIResult<()> {
+        loop {
+            let result = connectivity::check(self.config.connectivity());
+
+            if result.is_ok() || Instant::now() >= deadline {
+                return result.context(errors::NetworkTimeoutSnafu {
+                    timeout: self.config.live_network().timeout(),
+                });
+            }
+            sleep(Duration::from_secs(2));
+        }
+    }
+
+    fn start_service(&self, service: &str) -> ALIResult<()> {
+        let status = Command::new("systemctl")
+            .args(&["start", service])
             .spawn()
             .unwrap()
             .wait()
             .unwrap();
 
-        let result = status.success();
-        Ok(result)
+        ensure!(
+            status.success(),
+            errors::CommandExecutionSnafu {
+                message: format!("systemctl start {}", service),
+            }
+        );
+        Ok(())
     }
 
-    fn connect_to_network(&self) -> ALIResult<()> {
-        let program = self.config.system().connection_command.get(0).unwrap();
-        let args = &self.config.system().connection_command[1..];
+    fn run_connection_command(&self) -> ALIResult<()> {
+        let (program, args) = match self.config.system().connection_command.split_first() {
+            Some(command) => command,
+            None => return errors::NetworkSnafu.fail(),
+        };
 
         if program == "nmcli" {
-            let mut network_manager_active = Command::new("systemctl")
-                .args(&["status", "NetworkManager"])
-                .stdout(Stdio::null())
-                .spawn()
-                .unwrap()
-                .wait()
-                .unwrap()
-                .success();
-
-            if !network_manager_active {
-                network_manager_active = Command::new("systemctl")
-                    .args(&["start", "NetworkManager"])
-                    .stdout(Stdio::null())
-                    .spawn()
-                    .unwrap()
-                    .wait()
-                    .unwrap()
-                    .success();
-            }
-            assert!(network_manager_active);
+            self.start_service("NetworkManager")?;
         }
 
         let status = Command::new(program)
@@ -298,6 +331,109 @@ impl<'a, 'b> Installer<'a, 'b> {
         Ok(())
     }
 
+    fn write_iwd_profile(&self) -> ALIResult<()> {
+        let live_network = self.config.live_network();
+        let passphrase = &live_network.passphrase;
+        let secured = passphrase.is_set();
+
+        let mut buffer = if secured {
+            iwd::profile(&passphrase.expose("the live Wi-Fi passphrase")?, false)
+        } else {
+            iwd::profile("", false)
+        };
+        passphrase.clear();
+
+        let path = iwd::profile_path(iwd::PROFILES_DIR, &live_network.ssid, secured);
+
+        fs::create_dir_all(iwd::PROFILES_DIR).unwrap();
+        fs::write(&path, &buffer).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        zeroize(&mut buffer);
+        Ok(())
+    }
+
+    /// The scan takes a while after iwd starts, so the network may not be
+    /// found on the first try.
+    fn connect_to_wifi(&self, deadline: Instant) -> ALIResult<()> {
+        let live_network = self.config.live_network();
+        let passphrase = &live_network.passphrase;
+
+        self.start_service("iwd")?;
+
+        if live_network.method() == WifiMethod::Profile {
+            self.write_iwd_profile()?;
+        }
+        loop {
+            let mut iwctl = Command::new("iwctl");
+
+            if live_network.method() == WifiMethod::Iwctl && passphrase.is_set() {
+                iwctl
+                    .arg("--passphrase")
+                    .secret_arg(&*passphrase.expose("the live Wi-Fi passphrase")?);
+            }
+            let status = iwctl
+                .args(&["station", live_network.interface(), "connect"])
+                .arg(&live_network.ssid)
+                .spawn()
+                .unwrap()
+                .wait()
+                .unwrap();
+
+            if status.success() {
+                break;
+            }
+            if Instant::now() >= deadline {
+                passphrase.clear();
+                let message = format!(
+                    "iwctl station {} connect {}",
+                    live_network.interface(),
+                    live_network.ssid
+                );
+                return errors::CommandExecutionSnafu { message }.fail().context(
+                    errors::NetworkTimeoutSnafu {
+                        timeout: live_network.timeout(),
+                    },
+                );
+            }
+            sleep(Duration::from_secs(2));
+        }
+        passphrase.clear();
+        Ok(())
+    }
+
+    fn connect_to_network(&self) -> ALIResult<()> {
+        let live_network = self.config.live_network();
+        let deadline = Instant::now() + Duration::from_secs(live_network.timeout());
+
+        match live_network.kind() {
+            LiveNetworkKind::None => self.run_connection_command()?,
+            // archiso configures wired interfaces with DHCP through networkd.
+            LiveNetworkKind::Ethernet => {
+                self.start_service("systemd-networkd")?;
+                self.start_service("systemd-resolved")?;
+            }
+            LiveNetworkKind::Wifi => self.connect_to_wifi(deadline)?,
+        }
+        self.wait_for_network(deadline)
+    }
+
+    /// Lets the installed system join the Wi-Fi network of the installation
+    /// on the first boot.
+    fn copy_live_network_profile(&self) {
+        let live_network = self.config.live_network();
+
+        if live_network.kind() != LiveNetworkKind::Wifi || !live_network.copy_profile() {
+            return;
+        }
+        let secured = live_network.passphrase.is_set();
+        let profile = iwd::profile_path(iwd::PROFILES_DIR, &live_network.ssid, secured);
+        let target = iwd::profile_path("/mnt/var/lib/iwd", &live_network.ssid, secured);
+
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+        fs::copy(&profile, &target).unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+    }
+
     pub(crate) fn check_drive(&mut self) -> ALIResult<&mut Self> {
         let device = self.config.system().drive_path();
         ensure!(device.exists(), errors::DeviceNotFoundSnafu { device });
@@ -351,6 +487,7 @@ impl<'a, 'b> Installer<'a, 'b> {
 
     pub(crate) fn partition(&mut self) -> ALIResult<&mut Self> {
         let drive_path = self.config.system().drive_path();
+        let swap = self.config.swap();
 
         if !partition_table_exists(&drive_path) {
             self.create_partition_table();
@@ -377,6 +514,9 @@ impl<'a, 'b> Installer<'a, 'b> {
         if self.partitions.boot().is_none() {
             add_boot_partition(&mut gpt, unused_indexes.pop().unwrap());
         }
+        if swap.kind() == SwapKind::Partition && self.partitions.swap().is_none() {
+            add_swap_partition(&mut gpt, unused_indexes.pop().unwrap(), swap.size());
+        }
         if self.partitions.root().is_none() {
             add_root_partition(&mut gpt, unused_indexes.pop().unwrap());
         }
@@ -397,24 +537,33 @@ impl<'a, 'b> Installer<'a, 'b> {
         assert!(self.partitions.boot().is_some());
         assert!(self.partitions.root().is_some());
 
+        if swap.kind() == SwapKind::Partition {
+            assert!(self.partitions.swap().is_some());
+        }
+
         Ok(self)
     }
 
-    pub(crate) fn encrypt_partition(&mut self) -> &mut Self {
-        let mut password = self.config.partitions().root.password.to_owned();
-
-        if password.is_empty() {
-            password.truncate(0);
-            println!("Type the password for the disk decryption");
-            stdin().read_line(&mut password).unwrap();
-        } else {
-            password.push('\n');
+    pub(crate) fn encrypt_partition(&mut self) -> ALIResult<&mut Self> {
+        let config = self.config;
+        let password = &config.partitions().root.password;
+
+        if !password.is_set() {
+            let mut password = read_new_password("Type the password for the disk decryption")?;
+            println!("Encrypting the disk...");
+            self.luks_format(&password);
+            self.luks_open(&password);
+            zeroize(&mut password);
+            return Ok(self);
         }
+        let exposed = password.expose("the disk encryption password")?;
 
         println!("Encrypting the disk...");
-        self.luks_format(&password);
-        self.luks_open(&password);
-        self
+        self.luks_format(&exposed);
+        self.luks_open(&exposed);
+        drop(exposed);
+        password.clear();
+        Ok(self)
     }
 
     pub(crate) fn format_partitions(&mut self) -> &mut Self {
@@ -439,6 +588,17 @@ impl<'a, 'b> Installer<'a, 'b> {
 
         assert!(status.success());
 
+        if self.config.swap().kind() == SwapKind::Partition {
+            let status = Command::new("mkswap")
+                .arg(self.partitions.swap().unwrap())
+                .spawn()
+                .unwrap()
+                .wait()
+                .unwrap();
+
+            assert!(status.success());
+        }
+
         if self.efi && !self.efi_formatted {
             let status = Command::new("mkfs.fat")
                 .arg("-F32")
@@ -467,7 +627,72 @@ impl<'a, 'b> Installer<'a, 'b> {
         self
     }
 
+    fn create_swap_file(&self, path: &Path) {
+        let size = format!("{}M", self.config.swap().size_mib());
+
+        if self.config.partitions().root.fs == "btrfs" {
+            let status = Command::new("btrfs")
+                .args(&["filesystem", "mkswapfile", "--size", &size])
+                .arg(path)
+                .spawn()
+                .unwrap()
+                .wait()
+                .unwrap();
+
+            assert!(status.success());
+            return;
+        }
+        let status = Command::new("fallocate")
+            .args(&["-l", &size])
+            .arg(path)
+            .spawn()
+            .unwrap()
+            .wait()
+            .unwrap();
+
+        assert!(status.success());
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let status = Command::new("mkswap")
+            .arg(path)
+            .spawn()
+            .unwrap()
+            .wait()
+            .unwrap();
+
+        assert!(status.success());
+    }
+
+    /// Activates the swap, so genfstab picks it up.
+    pub(crate) fn enable_swap(&mut self) -> &mut Self {
+        let swap = self.config.swap();
+
+        let device = match swap.kind() {
+            SwapKind::None => return self,
+            SwapKind::Partition => self.partitions.swap().unwrap().clone(),
+            SwapKind::File => {
+                let mut path = PathBuf::from("/mnt");
+                path.push(swap.file().strip_prefix("/").unwrap());
+                self.create_swap_file(&path);
+                path
+            }
+        };
+        let status = Command::new("swapon")
+            .arg(&device)
+            .spawn()
+            .unwrap()
+            .wait()
+            .unwrap();
+
+        assert!(status.success());
+        self
+    }
+
     pub(crate) fn configure_mirrors(&mut self) -> &mut Self {
+        if self.config.offline().enabled() {
+            println!("Offline repository is used, skipping reflector");
+            return self;
+        }
         if !Path::new("/etc/pacman.d/mirrorlist.backup").exists() {
             fs::rename(
                 "/etc/pacman.d/mirrorlist",
@@ -475,17 +700,19 @@ impl<'a, 'b> Installer<'a, 'b> {
             )
             .unwrap();
         }
-        let status = Command::new("reflector")
-            .args(&[
-                "--protocol",
-                "https",
-                "--latest",
-                "8",
-                "--sort",
-                "rate",
-                "--save",
-                "/etc/pacman.d/mirrorlist",
-            ])
+        let mirrors = self.config.mirrors();
+
+        if let Some(mirrorlist) = mirrors.mirrorlist() {
+            fs::write("/etc/pacman.d/mirrorlist", mirrorlist).unwrap();
+            return self;
+        }
+        let mut reflector = Command::new("reflector");
+
+        for (option, value) in mirrors.reflector_args() {
+            reflector.args(&[option, &value]);
+        }
+        let status = reflector
+            .args(&["--save", "/etc/pacman.d/mirrorlist"])
             .spawn()
             .unwrap()
             .wait()
@@ -495,33 +722,302 @@ impl<'a, 'b> Installer<'a, 'b> {
         self
     }
 
-    pub(crate) fn enable_multilib(&mut self) -> &mut Self {
-        let mut conf_content = String::new();
-        let mut pacman_conf = File::open("/etc/pacman.conf").unwrap();
-        pacman_conf.read_to_string(&mut conf_content).unwrap();
+    /// Applies the `[pacman]` options and repositories to the pacman.conf
+    /// of the live system or the target.
+    fn edit_pacman_conf<P: AsRef<Path>>(&self, path: P) {
+        let pacman = self.config.pacman();
+        let mut conf = PacmanConf::open(path);
+
+        if let Some(parallel_downloads) = pacman.parallel_downloads() {
+            conf.set_option("ParallelDownloads", Some(&parallel_downloads.to_string()));
+        }
+        if pacman.color() {
+            conf.set_option("Color", None);
+        }
+        if pacman.ilovecandy() {
+            conf.set_option("ILoveCandy", None);
+        }
+        if !pacman.ignore_pkg().is_empty() {
+            conf.set_option("IgnorePkg", Some(&pacman.ignore_pkg().join(" ")));
+        }
+        if pacman.multilib() {
+            conf.enable_repo("multilib");
+        }
+        for repository in self.config.repositories() {
+            conf.add_repo(
+                &repository.name,
+                repository.sig_level.as_deref(),
+                &repository.servers,
+            );
+        }
+        conf.save();
+    }
+
+    /// Resolves every package name before the disk is touched. The sync
+    /// databases are downloaded to a temporary directory with the configured
+    /// repositories and mirrors, signatures aren't checked as the keys may
+    /// not be imported yet.
+    fn check_packages(&self) -> ALIResult<()> {
+        let check = self.config.package_check();
+        let offline = self.config.offline();
+
+        if !check.enable() {
+            return Ok(());
+        }
+        let dir = package_check::WorkDir::new(env::temp_dir().join("installer-package-check"));
+        let dbpath = dir.path().join("db");
+        let conf = dir.path().join("pacman.conf");
+
+        fs::create_dir(&dbpath).unwrap();
+
+        if offline.enabled() {
+            fs::write(&conf, offline.pacman_conf(&offline.server())).unwrap();
+        } else {
+            fs::copy("/etc/pacman.conf", &conf).unwrap();
+            self.edit_pacman_conf(&conf);
+        }
+        let mut pacman_conf = PacmanConf::open(&conf);
+
+        if let Some(mirrorlist) = self.config.mirrors().mirrorlist() {
+            let path = dir.path().join("mirrorlist");
+            fs::write(&path, mirrorlist).unwrap();
+            pacman_conf.set_mirrorlist(path);
+        }
+        pacman_conf.set_sig_level("Never");
+        pacman_conf.save();
+
+        println!("Checking package names");
+        package_check::sync(&conf, &dbpath)?;
+
+        let packages = self.config.packages();
+        let mut unknown = vec![];
+
+        for list in &[
+            packages.pacman_system(),
+            packages.pacman(),
+            packages.archiso(),
+        ] {
+            for package in package_check::unknown_in_repos(&conf, &dbpath, list)? {
+                if !unknown.contains(&package) {
+                    unknown.push(package);
+                }
+            }
+        }
+        // The AUR is skipped by offline installs.
+        if !offline.enabled() {
+            let mut aur = packages.aur();
+            let helper = self.config.system().aur_helper();
 
-        let conf_content = conf_content.replace(
-            "#[multilib]\n#Include = /etc/pacman.d/mirrorlist",
-            "[multilib]\nInclude = /etc/pacman.d/mirrorlist",
+            if !helper.is_empty() {
+                aur.push(helper);
+            }
+            unknown.extend(package_check::unknown_in_aur(check.aur_rpc(), &aur)?);
+        }
+        ensure!(
+            unknown.is_empty(),
+            errors::UnknownPackagesSnafu { packages: unknown }
         );
-        let mut pacman_conf = File::create("/etc/pacman.conf").unwrap();
-        pacman_conf.write_all(conf_content.as_bytes()).unwrap();
-        self
+        Ok(())
     }
 
-    pub(crate) fn pacstrap(&mut self) -> &mut Self {
-        let packages = self.config.packages().pacman_system();
+    /// pacman-init.service of the ISO creates the keyring on boot, racing
+    /// with the installer otherwise. Starting it waits for the oneshot unit.
+    fn wait_for_pacman_init(&self) -> ALIResult<()> {
+        let output = Command::new("systemctl")
+            .args(&[
+                "show",
+                "--property=LoadState",
+                "--value",
+                "pacman-init.service",
+            ])
+            .output()
+            .unwrap();
 
-        let status = Command::new("pacstrap")
-            .arg("/mnt")
-            .args(&packages)
+        if String::from_utf8_lossy(&output.stdout).trim() != "loaded" {
+            return Ok(());
+        }
+        let status = Command::new("systemctl")
+            .args(&["start", "pacman-init.service"])
             .spawn()
             .unwrap()
             .wait()
             .unwrap();
 
-        assert!(status.success());
-        self
+        ensure!(
+            status.success(),
+            errors::CommandExecutionSnafu {
+                message: "systemctl start pacman-init.service".to_owned(),
+            }
+        );
+        Ok(())
+    }
+
+    pub(crate) fn init_keyring(&mut self) -> ALIResult<&mut Self> {
+        let keyring = self.config.keyring();
+
+        self.wait_for_pacman_init()?;
+        pacman_key::pacman_key(None, &["--init"])?;
+        pacman_key::pacman_key(None, &["--populate"])?;
+
+        let mut pacman = Command::new("pacman");
+
+        // The install script of the package populates the new keys.
+        if let Some(package) = keyring.package() {
+            pacman.args(&["-U", "--noconfirm"]).arg(package);
+        } else if keyring.refresh() {
+            pacman.args(&["-Sy", "--noconfirm", "--needed", "archlinux-keyring"]);
+        } else {
+            return Ok(self);
+        }
+        let status = pacman.spawn().unwrap().wait().unwrap();
+
+        ensure!(
+            status.success(),
+            errors::CommandExecutionSnafu {
+                message: "update archlinux-keyring".to_owned(),
+            }
+        );
+        Ok(self)
+    }
+
+    /// Imports the signing keys of the repositories into the keyring in
+    /// `gpgdir`, the live one by default.
+    fn import_repository_keys(&self, gpgdir: Option<&Path>) -> ALIResult<()> {
+        for repository in self.config.repositories() {
+            pacman_key::import_key(
+                gpgdir,
+                repository.key_file.as_deref(),
+                repository.key_id.as_deref(),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn configure_pacman(&mut self) -> ALIResult<&mut Self> {
+        self.import_repository_keys(None)?;
+        self.edit_pacman_conf("/etc/pacman.conf");
+        Ok(self)
+    }
+
+    pub(crate) fn pacstrap(&mut self) -> ALIResult<&mut Self> {
+        let packages = self.config.packages().pacman_system();
+        let offline = self.config.offline();
+
+        // The config isn't copied to the target, which gets the default one
+        // with the regular mirrors.
+        let config = if offline.enabled() {
+            let path = env::temp_dir().join(OFFLINE_PACMAN_CONF);
+            fs::write(&path, offline.pacman_conf(&offline.server())).unwrap();
+            Some(path)
+        } else {
+            None
+        };
+        pacman::run_with_retries(Path::new("/mnt"), config.as_deref(), || {
+            let mut pacstrap = Command::new("pacstrap");
+
+            if let Some(config) = &config {
+                pacstrap.arg("-C").arg(config);
+            }
+            pacstrap.arg("/mnt").args(&packages);
+            pacstrap
+        })?;
+        self.install_target_mirrorlist();
+        self.import_repository_keys(Some(Path::new("/mnt/etc/pacman.d/gnupg")))?;
+        self.edit_pacman_conf("/mnt/etc/pacman.conf");
+        Ok(self)
+    }
+
+    /// Gives the target the configured mirrors, also when the live system
+    /// used the offline repository.
+    fn install_target_mirrorlist(&self) {
+        let mirrors = self.config.mirrors();
+        let target = "/mnt/etc/pacman.d/mirrorlist";
+
+        if let Some(mirrorlist) = mirrors.mirrorlist() {
+            fs::write(target, mirrorlist).unwrap();
+        } else if !self.config.offline().enabled() {
+            fs::copy("/etc/pacman.d/mirrorlist", target).unwrap();
+        }
+        // Lets the machine be rebuilt with the same package versions.
+        if let Some(date) = mirrors.snapshot_date() {
+            fs::create_dir_all("/mnt/var/lib/installer").unwrap();
+            fs::write(
+                "/mnt/var/lib/installer/snapshot",
+                format!("{}\n{}\n", date, mirrors.snapshot_server().unwrap()),
+            )
+            .unwrap();
+        }
+    }
+
+    /// Gives the target the regular mirrors once the packages of the snapshot
+    /// are installed, a pinned mirrorlist would keep it from updating.
+    fn restore_target_mirrorlist(&self) {
+        if self.config.mirrors().snapshot_date().is_none() {
+            return;
+        }
+        // The mirrorlist of the live system before the snapshot replaced it.
+        let backup = Path::new("/etc/pacman.d/mirrorlist.backup");
+        let source = if backup.exists() {
+            backup
+        } else {
+            Path::new("/etc/pacman.d/mirrorlist")
+        };
+        fs::copy(source, "/mnt/etc/pacman.d/mirrorlist").unwrap();
+    }
+
+    /// Makes the offline repository available to pacman inside the chroot.
+    fn prepare_offline_repo(&self) {
+        let offline = self.config.offline();
+
+        if !offline.enabled() {
+            return;
+        }
+        let server = match offline.local_dir() {
+            Some(dir) => {
+                let mount_point = format!("/mnt{}", OFFLINE_REPO_DIR);
+                fs::create_dir_all(&mount_point).unwrap();
+
+                let status = Command::new("mount")
+                    .args(&["--bind", "--options", "ro"])
+                    .arg(dir)
+                    .arg(&mount_point)
+                    .spawn()
+                    .unwrap()
+                    .wait()
+                    .unwrap();
+
+                assert!(status.success());
+                format!("file://{}", OFFLINE_REPO_DIR)
+            }
+            None => offline.server(),
+        };
+        let mut path = PathBuf::from("/mnt/root/installer");
+        path.push(OFFLINE_PACMAN_CONF);
+        fs::write(path, offline.pacman_conf(&server)).unwrap();
+    }
+
+    fn cleanup_offline_repo(&self) {
+        let offline = self.config.offline();
+
+        if !offline.enabled() {
+            return;
+        }
+        if offline.local_dir().is_some() {
+            let mount_point = format!("/mnt{}", OFFLINE_REPO_DIR);
+
+            let status = Command::new("umount")
+                .arg(&mount_point)
+                .spawn()
+                .unwrap()
+                .wait()
+                .unwrap();
+
+            assert!(status.success());
+            fs::remove_dir(mount_point).unwrap();
+        }
+        let mut path = PathBuf::from("/mnt/root/installer");
+        path.push(OFFLINE_PACMAN_CONF);
+        fs::remove_file(path).unwrap();
     }
 
     pub(crate) fn generate_fstab(&mut self) -> &mut Self {
@@ -542,7 +1038,46 @@ impl<'a, 'b> Installer<'a, 'b> {
         self
     }
 
-    pub(crate) fn chroot(&mut self) {
+    /// `file:` secrets are read on the live system, the target gets copies
+    /// readable by root for the time of the chroot.
+    fn stage_secret_files(&self) -> ALIResult<()> {
+        for path in self
+            .config
+            .chroot_secrets()
+            .iter()
+            .filter_map(|secret| secret.file())
+        {
+            let staged = Path::new("/mnt").join(staged_path(path).strip_prefix("/").unwrap());
+            let mut value = fs::read_to_string(path).context(errors::SecretFileSnafu { path })?;
+
+            fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(staged.parent().unwrap())
+                .unwrap();
+            fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(&staged)
+                .unwrap()
+                .write_all(value.as_bytes())
+                .unwrap();
+            zeroize(&mut value);
+        }
+        Ok(())
+    }
+
+    fn remove_staged_secret_files(&self) {
+        let dir = Path::new("/mnt").join(SECRETS_DIR.trim_start_matches('/'));
+
+        if dir.exists() {
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    pub(crate) fn chroot(&mut self) -> ALIResult<&mut Self> {
         let status = Command::new("cp")
             .arg("-rv")
             .arg(exe_dir())
@@ -553,33 +1088,66 @@ impl<'a, 'b> Installer<'a, 'b> {
             .unwrap();
 
         assert!(status.success());
+        self.copy_live_network_profile();
+        self.prepare_offline_repo();
+        self.stage_secret_files()?;
 
-        let status = Command::new("arch-chroot")
+        let mut arch_chroot = Command::new("arch-chroot");
+        let status = arch_chroot
             .args(&["/mnt", "/root/installer/cli", "chroot-install"])
             .spawn()
             .unwrap()
             .wait()
             .unwrap();
 
-        assert!(status.success());
+        // Cleaned up whatever the outcome, the target is left as it'd be
+        // after a successful run.
+        self.remove_staged_secret_files();
+        self.cleanup_offline_repo();
+        // Left behind when stage 2 is interrupted.
+        TemporaryNopasswd::remove_stale_in(Path::new("/mnt"))?;
+
+        ensure!(
+            status.success(),
+            errors::CommandExecutionSnafu {
+                message: arch_chroot.command_line(),
+            }
+        );
+        self.restore_target_mirrorlist();
+
+        // Inside the chroot the file is a bind mount of the live one.
+        if self.config.network().resolved().enable {
+            let resolv_conf = Path::new("/mnt/etc/resolv.conf");
+
+            if resolv_conf.symlink_metadata().is_ok() {
+                fs::remove_file(resolv_conf).unwrap();
+            }
+            symlink("/run/systemd/resolve/stub-resolv.conf", resolv_conf).unwrap();
+        }
+        Ok(self)
     }
 
     pub(crate) fn run(&mut self) -> ALIResult<()> {
         let encryption = self.config.partitions().root.encryption;
-        let multilib = self.config.system().multilib();
 
-        if !self.network_available()? {
+        // A local repository needs no network.
+        if self.config.offline().local_dir().is_none() && !self.network_available()? {
             self.connect_to_network()?;
         }
         self.check_drive()?;
         // .check_mounted()?
         // .check_luks_open()?
 
+        if self.steps.contains(&InstallStep::Pacstrap) || self.steps.contains(&InstallStep::Chroot)
+        {
+            self.check_packages()?;
+        }
+
         if self.steps.contains(&InstallStep::Partition) {
             self.partition()?;
         }
         if self.steps.contains(&InstallStep::Encrypt) && encryption {
-            self.encrypt_partition();
+            self.encrypt_partition()?;
         }
         if self.steps.contains(&InstallStep::Format) {
             self.format_partitions();
@@ -587,20 +1155,26 @@ impl<'a, 'b> Installer<'a, 'b> {
         if self.steps.contains(&InstallStep::Mount) {
             self.mount_partitions();
         }
+        if self.steps.contains(&InstallStep::Swap) {
+            self.enable_swap();
+        }
         if self.steps.contains(&InstallStep::Mirrors) {
             self.configure_mirrors();
         }
-        if self.steps.contains(&InstallStep::Multilib) && multilib {
-            self.enable_multilib();
+        if self.steps.contains(&InstallStep::Keyring) {
+            self.init_keyring()?;
+        }
+        if self.steps.contains(&InstallStep::PacmanConf) {
+            self.configure_pacman()?;
         }
         if self.steps.contains(&InstallStep::Pacstrap) {
-            self.pacstrap();
+            self.pacstrap()?;
         }
         if self.steps.contains(&InstallStep::Fstab) {
             self.generate_fstab();
         }
         if self.steps.contains(&InstallStep::Chroot) {
-            self.chroot();
+            self.chroot()?;
         }
         Ok(())
     }
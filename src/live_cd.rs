@@ -3,12 +3,17 @@ use snafu::{ensure, OptionExt};
 use crate::errors;
 
 use {
-    crate::{config::Config, errors::ALIResult, utils::check_su, utils::command::Command},
+    crate::{
+        config::{Config, SerialConsole},
+        errors::ALIResult,
+        utils::check_su,
+        utils::command::Command,
+    },
     std::{
         env::var,
         fs::{self, create_dir_all, read_dir, File},
         io::prelude::*,
-        os::unix::fs::PermissionsExt,
+        os::unix::fs::{symlink, PermissionsExt},
         path::PathBuf,
     },
 };
@@ -134,6 +139,97 @@ impl<'a> LiveCreator<'a> {
         self
     }
 
+    /// Appends the parameters to the kernel command lines of the boot loader
+    /// entries in the directory.
+    fn append_kernel_params(&self, dir: &str, prefix: &str, params: &str) {
+        let mut dir_path = self.profile_root.clone();
+        dir_path.push(dir);
+
+        let entries = match read_dir(&dir_path) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries {
+            let path = entry.unwrap().path();
+
+            if !path.is_file() {
+                continue;
+            }
+            let buffer = fs::read_to_string(&path).unwrap();
+            let mut lines: Vec<String> = vec![];
+
+            for line in buffer.lines() {
+                if line.trim_start().starts_with(prefix) {
+                    lines.push(format!("{} {}", line, params));
+                } else {
+                    lines.push(line.to_owned());
+                }
+            }
+            lines.push(String::new());
+            fs::write(&path, lines.join("\n")).unwrap();
+        }
+    }
+
+    /// Puts the lines at the top of the boot loader config, where the
+    /// terminal settings must come before the menu.
+    fn prepend_to_config(&self, file: &str, lines: &str) {
+        let mut path = self.profile_root.clone();
+        path.push(file);
+
+        let buffer = match fs::read_to_string(&path) {
+            Ok(buffer) => buffer,
+            Err(_) => return,
+        };
+        if !buffer.starts_with(lines) {
+            fs::write(&path, format!("{}{}", lines, buffer)).unwrap();
+        }
+    }
+
+    fn configure_serial_console(&mut self, console: Option<SerialConsole>) -> ALIResult<&mut Self> {
+        let console = match console {
+            Some(console) => console,
+            None => return Ok(self),
+        };
+        // The live system is built without validating the whole config.
+        ensure!(
+            console.unit().is_some(),
+            errors::ConfigInvalidSnafu {
+                desc: format!(
+                    "serial console must be a ttyS device, got {}",
+                    console.device
+                ),
+            }
+        );
+        let params = console.kernel_params().join(" ");
+
+        self.append_kernel_params("efiboot/loader/entries", "options ", &params);
+        self.append_kernel_params("syslinux", "APPEND ", &params);
+        self.append_kernel_params("grub", "linux ", &params);
+
+        // The boot menus are shown on the serial console as well.
+        self.prepend_to_config(
+            "syslinux/syslinux.cfg",
+            &format!("{}\n", console.syslinux_directive()),
+        );
+        self.prepend_to_config(
+            "grub/grub.cfg",
+            &format!(
+                "{}\nterminal_input console serial\nterminal_output console serial\n",
+                console.grub_command()
+            ),
+        );
+
+        let mut wants = self.profile_root.clone();
+        wants.push("airootfs/etc/systemd/system/getty.target.wants");
+        create_dir_all(&wants).unwrap();
+        wants.push(format!("serial-getty@{}.service", console.device));
+
+        if wants.symlink_metadata().is_err() {
+            symlink("/usr/lib/systemd/system/serial-getty@.service", &wants).unwrap();
+        }
+        Ok(self)
+    }
+
     fn build(&mut self) -> &mut Self {
         let program = "mkarchiso";
         let profile_dir = self.profile_root.clone();
@@ -211,6 +307,7 @@ pub fn main(config: &Config) -> ALIResult<()> {
         .copy_archiso_files()
         .copy_installer(config.live_cd.installer_location())
         .add_packages(config.packages().archiso())
+        .configure_serial_console(config.system().serial_console())?
         .build()
         .copy_iso()
         .change_iso_owner()
@@ -6,7 +6,10 @@ use {
         errors::ALIResult,
         install, live_cd,
         log::init_logger,
-        utils::exe_dir,
+        utils::{
+            exe_dir,
+            passwd::{mkpasswd, HashMethod},
+        },
     },
     std::{fs::File, io::prelude::*, path::PathBuf, process::exit},
 };
@@ -20,8 +23,10 @@ Values:
     encrypt
     format
     mount
+    swap
     mirrors
-    multilib
+    keyring
+    pacman-conf (alias: multilib)
     pacstrap
     fstab
     chroot";
@@ -49,6 +54,15 @@ enum Command {
 
     #[clap(about = "Begins install in chroot environment")]
     ChrootInstall {},
+
+    #[clap(
+        name = "mkpasswd",
+        about = "Prints a password hash to use in config instead of plaintext"
+    )]
+    MkPasswd {
+        #[clap(long, default_value = "yescrypt", help = "Either yescrypt or sha-512")]
+        method: HashMethod,
+    },
 }
 
 #[derive(clap::Parser, Debug)]
@@ -90,14 +104,17 @@ fn run(args: &Args, conf_path: &mut PathBuf) -> ALIResult<()> {
             println!("Encrypt: {}", steps.contains(&InstallStep::Encrypt));
             println!("Format: {}", steps.contains(&InstallStep::Format));
             println!("Mount: {}", steps.contains(&InstallStep::Mount));
+            println!("Swap: {}", steps.contains(&InstallStep::Swap));
             println!("Mirrors: {}", steps.contains(&InstallStep::Mirrors));
-            println!("Multilib: {}", steps.contains(&InstallStep::Multilib));
+            println!("Keyring: {}", steps.contains(&InstallStep::Keyring));
+            println!("Pacman.conf: {}", steps.contains(&InstallStep::PacmanConf));
             println!("Pacstrap: {}", steps.contains(&InstallStep::Pacstrap));
             println!("Fstab: {}", steps.contains(&InstallStep::Fstab));
             println!("Chroot: {}", steps.contains(&InstallStep::Chroot));
 
             let mut config = Config::new(conf_path, None)?;
             config.validate()?;
+            config.validate_live()?;
             install::main(&mut config, steps)?;
         }
         Command::ChrootInstall {} => {
@@ -105,6 +122,9 @@ fn run(args: &Args, conf_path: &mut PathBuf) -> ALIResult<()> {
             config.validate()?;
             chroot_install::main(&config)?;
         }
+        Command::MkPasswd { method } => {
+            println!("{}", mkpasswd(method)?);
+        }
     }
     Ok(())
 }
@@ -3,92 +3,114 @@ use crate::{config::Config, errors::ALIResult};
 pub(crate) mod stage2_package_configurator;
 
 mod private {
+    use snafu::ensure;
+
     use {
         super::stage2_package_configurator::Stage2PackageConfigurator,
         crate::{
             config::Config,
-            utils::{pacman_install, su_command, sudo_passwd_off, sudo_passwd_on},
+            errors::{self, ALIResult},
+            utils::{git_clone, pacman_install, run_as::RunAs, sudoers::TemporaryNopasswd},
         },
-        std::env::{current_dir, set_current_dir},
     };
 
     pub(crate) struct Stage2ChrootInstaller<'a> {
         config: &'a Config,
+        /// AUR packages and extensions, one broken package shouldn't abort
+        /// the whole installation.
+        failed: Vec<String>,
     }
 
     impl<'a> Stage2ChrootInstaller<'a> {
         pub(crate) fn new(config: &'a Config) -> Stage2ChrootInstaller {
-            Stage2ChrootInstaller { config }
+            Stage2ChrootInstaller {
+                config,
+                failed: vec![],
+            }
+        }
+
+        pub(crate) fn install_packages(&mut self) -> ALIResult<&mut Self> {
+            pacman_install(&self.config.packages().pacman())?;
+            Ok(self)
         }
 
-        pub(crate) fn install_packages(&mut self) -> &mut Self {
-            pacman_install(&self.config.packages().pacman());
-            self
+        pub(crate) fn configure_packages(&mut self) -> ALIResult<&mut Self> {
+            let failed = Stage2PackageConfigurator::new(self.config).run()?;
+            self.failed.extend(failed);
+            Ok(self)
         }
 
-        pub(crate) fn configure_packages(&mut self) -> &mut Self {
-            Stage2PackageConfigurator::new(self.config).run();
-            self
+        pub(crate) fn remove_stale_nopasswd(&mut self) -> ALIResult<&mut Self> {
+            TemporaryNopasswd::remove_stale()?;
+            Ok(self)
         }
 
-        pub(crate) fn install_aur_helper(&mut self) -> &mut Self {
+        pub(crate) fn install_aur_helper(&mut self) -> ALIResult<&mut Self> {
             let helper = self.config.system().aur_helper();
 
             if helper.is_empty() {
-                return self;
+                return Ok(self);
+            }
+            if self.config.offline().enabled() {
+                println!("Offline install, skipping AUR helper {}", helper);
+                return Ok(self);
             }
-            pacman_install(&["base-devel"]);
+            pacman_install(&["base-devel"])?;
             let working_dir = format!("/tmp/{}", helper);
             let url = format!("https://aur.archlinux.org/{}.git", helper);
-            let user = self.config.system().arch_username();
-
-            su_command(user, "git", &["clone", &url, &working_dir])
-                .spawn()
-                .unwrap()
-                .wait()
-                .unwrap();
-
-            let installer_dir = current_dir().unwrap();
-            set_current_dir(working_dir).unwrap();
-            sudo_passwd_off(user);
-
-            su_command(user, "makepkg", &["-si", "--noconfirm"])
-                .spawn()
-                .unwrap()
-                .wait()
-                .unwrap();
-
-            sudo_passwd_on(user);
-            set_current_dir(installer_dir).unwrap();
-            self
+            let username = &self.config.primary_user().name;
+            let user = RunAs::new(username)?;
+
+            git_clone(&user, &url, &working_dir)?;
+
+            let _nopasswd = TemporaryNopasswd::new(username)?;
+            user.run_in(&working_dir, "makepkg", &["-si", "--noconfirm"])?;
+            Ok(self)
         }
 
-        pub(crate) fn install_aur_packages(&mut self) -> &mut Self {
+        pub(crate) fn install_aur_packages(&mut self) -> ALIResult<&mut Self> {
             let cmd = self.config.system().aur_helper();
-            let user = self.config.system().arch_username();
+            let user = &self.config.primary_user().name;
 
             if cmd.is_empty() {
-                return self;
+                return Ok(self);
+            }
+            if self.config.offline().enabled() {
+                println!("Offline install, skipping AUR packages");
+                return Ok(self);
+            }
+            let _nopasswd = TemporaryNopasswd::new(user)?;
+            let user = RunAs::new(user)?;
+
+            for pkg in self.config.packages().aur() {
+                if let Err(error) = user.run(cmd, &["-S", "--noconfirm", pkg]) {
+                    eprintln!("{}", error);
+                    self.failed.push(format!("AUR package {}", pkg));
+                }
             }
-            sudo_passwd_off(user);
-            self.config.packages().aur().into_iter().for_each(|pkg| {
-                su_command(user, cmd, &["-S", "--noconfirm", pkg])
-                    .spawn()
-                    .unwrap()
-                    .wait()
-                    .unwrap();
-            });
-            sudo_passwd_on(user);
-            self
+            Ok(self)
+        }
+
+        /// Reports everything which failed to install at the end.
+        pub(crate) fn check_failed(&mut self) -> ALIResult<&mut Self> {
+            ensure!(
+                self.failed.is_empty(),
+                errors::InstallIncompleteSnafu {
+                    failed: self.failed.clone(),
+                }
+            );
+            Ok(self)
         }
     }
 }
 
 pub(crate) fn main(config: &Config) -> ALIResult<()> {
     private::Stage2ChrootInstaller::new(config)
-        .install_packages()
-        .configure_packages()
-        .install_aur_helper()
-        .install_aur_packages();
+        .remove_stale_nopasswd()?
+        .install_packages()?
+        .configure_packages()?
+        .install_aur_helper()?
+        .install_aur_packages()?
+        .check_failed()?;
     Ok(())
 }
@@ -2,28 +2,32 @@ use crate::errors::ALIResult;
 
 use {
     crate::{
-        config::Config,
-        utils::{command::Command, git_clone, pacman_install, su_command, Service},
+        config::{zeroize, Config},
+        utils::{command::Command, git_clone, pacman_install, run_as::RunAs, Service},
     },
     std::{
         fs::{self, create_dir_all, File},
         io::prelude::*,
+        mem,
         path::{Path, PathBuf},
     },
 };
 
 pub(crate) struct Stage2PackageConfigurator<'a> {
     config: &'a Config,
+    failed: Vec<String>,
 }
 
 impl<'a> Stage2PackageConfigurator<'a> {
     pub(crate) fn new(config: &'a Config) -> Stage2PackageConfigurator {
-        Stage2PackageConfigurator { config }
+        Stage2PackageConfigurator {
+            config,
+            failed: vec![],
+        }
     }
 
     fn home<P: AsRef<Path>>(&self, path: Option<P>) -> PathBuf {
-        let mut full_path = PathBuf::from("/home");
-        full_path.push(self.config.system().arch_username());
+        let mut full_path = self.config.primary_user().home();
 
         if let Some(path) = path {
             full_path.push(path);
@@ -32,7 +36,7 @@ impl<'a> Stage2PackageConfigurator<'a> {
     }
 
     fn chown_to_user<P: AsRef<Path>>(&self, path: P) {
-        let mut user = self.config.system().arch_username().to_owned();
+        let mut user = self.config.primary_user().name.to_owned();
         user.push(':');
 
         let status = Command::new("chown")
@@ -46,27 +50,30 @@ impl<'a> Stage2PackageConfigurator<'a> {
         assert!(status.success());
     }
 
-    pub(crate) fn run(&mut self) {
+    /// Returns what failed to install without stopping the rest, such as
+    /// VS Code extensions.
+    pub(crate) fn run(&mut self) -> ALIResult<Vec<String>> {
         let packages = self.config.packages().pacman_system();
 
         if packages.contains(&"zsh") {
-            self.configure_zsh().unwrap();
+            self.configure_zsh()?;
         }
         if packages.contains(&"code") {
-            self.configure_code();
+            self.configure_code()?;
         }
         if packages.contains(&"shadowsocks-libev") {
-            self.configure_shadowsocks_libev();
+            self.configure_shadowsocks_libev()?;
         }
         if packages.contains(&"gvfs-google") {
-            self.configure_gvfs_google();
+            self.configure_gvfs_google()?;
         }
         if packages.contains(&"virt-manager") {
-            self.configure_virt_manager();
+            self.configure_virt_manager()?;
         }
         if packages.contains(&"gdm") {
             self.configure_gdm();
         }
+        Ok(mem::take(&mut self.failed))
     }
 
     fn configure_zsh(&mut self) -> ALIResult<&mut Self> {
@@ -80,34 +87,42 @@ impl<'a> Stage2PackageConfigurator<'a> {
 
             assert!(status.success());
         };
-        let username = self.config.system().arch_username();
+        let username = &self.config.primary_user().name;
         change_shell("root");
         change_shell(username);
         fs::rename("/root/installer/zshrc", self.home(Some(".zshrc"))).unwrap();
         self.chown_to_user(self.home(Some(".zshrc")));
 
+        if self.config.offline().enabled() {
+            println!("Offline install, skipping oh-my-zsh");
+            return Ok(self);
+        }
+        let user = RunAs::new(username)?;
         git_clone(
+            &user,
             "https://github.com/ohmyzsh/ohmyzsh.git",
             self.home(Some(".oh-my-zsh")),
         )?;
         git_clone(
+            &user,
             "https://github.com/zsh-users/zsh-syntax-highlighting.git",
             self.home(Some(".oh-my-zsh/custom/plugins/zsh-syntax-highlighting")),
         )?;
         git_clone(
+            &user,
             "https://github.com/zsh-users/zsh-autosuggestions.git",
             self.home(Some(".oh-my-zsh/custom/plugins/zsh-autosuggestions")),
         )?;
         git_clone(
+            &user,
             "https://github.com/bhilburn/powerlevel9k.git",
             self.home(Some(".oh-my-zsh/custom/themes/powerlevel9k")),
         )?;
-        self.chown_to_user(self.home(Some(".oh-my-zsh")));
         Ok(self)
     }
 
-    fn configure_code(&mut self) -> &mut Self {
-        pacman_install(&["ttf-droid", "ttf-ubuntu-font-family"]);
+    fn configure_code(&mut self) -> ALIResult<&mut Self> {
+        pacman_install(&["ttf-droid", "ttf-ubuntu-font-family"])?;
         create_dir_all("/root/.config/Code - OSS/User").unwrap();
 
         fs::rename(
@@ -116,10 +131,9 @@ impl<'a> Stage2PackageConfigurator<'a> {
         )
         .unwrap();
 
-        let username = self.config.system().arch_username();
-        let mut config_path = PathBuf::from("/home");
-        config_path.push(username);
-        config_path.push(".config");
+        let username = &self.config.primary_user().name;
+        let user = RunAs::new(username)?;
+        let config_path = user.home().join(".config");
 
         let mut path = config_path.clone();
         path.push("Code - OSS/User");
@@ -138,13 +152,12 @@ impl<'a> Stage2PackageConfigurator<'a> {
         assert!(status.success());
         let packages = self.config.packages().vscode();
 
-        packages.iter().for_each(|pkg| {
-            su_command(username, "code", &["--install-extension", pkg])
-                .spawn()
-                .unwrap()
-                .wait()
-                .unwrap();
-        });
+        for pkg in &packages {
+            if let Err(error) = user.run("code", &["--install-extension", pkg]) {
+                eprintln!("{}", error);
+                self.failed.push(format!("VS Code extension {}", pkg));
+            }
+        }
         if packages
             .iter()
             .any(|&pkg| pkg == "robbowen.synthwave-vscode")
@@ -157,10 +170,10 @@ impl<'a> Stage2PackageConfigurator<'a> {
                 .wait()
                 .unwrap();
         }
-        self
+        Ok(self)
     }
 
-    fn configure_shadowsocks_libev(&mut self) -> &mut Self {
+    fn configure_shadowsocks_libev(&mut self) -> ALIResult<&mut Self> {
         fs::rename(
             "/root/installer/ss-local.service",
             "/etc/systemd/system/ss-local.service",
@@ -189,27 +202,32 @@ impl<'a> Stage2PackageConfigurator<'a> {
 
         buffer = buffer.replace(
             "\"password\": \"\"",
-            &format!("\"password\": \"{}\"", password),
+            &format!(
+                "\"password\": \"{}\"",
+                &*password.expose("the shadowsocks password")?
+            ),
         );
+        password.clear();
 
         File::create("/etc/shadowsocks-libev/config.json")
             .unwrap()
             .write_all(buffer.as_bytes())
             .unwrap();
+        zeroize(&mut buffer);
 
         Service("ss-local").enable();
-        self
+        Ok(self)
     }
 
-    fn configure_gvfs_google(&mut self) -> &mut Self {
-        pacman_install(&["gnome-keyring"]);
-        self
+    fn configure_gvfs_google(&mut self) -> ALIResult<&mut Self> {
+        pacman_install(&["gnome-keyring"])?;
+        Ok(self)
     }
 
-    fn configure_virt_manager(&mut self) -> &mut Self {
-        pacman_install(&["dnsmasq", "ebtables", "qemu-headless"]);
+    fn configure_virt_manager(&mut self) -> ALIResult<&mut Self> {
+        pacman_install(&["dnsmasq", "ebtables", "qemu-headless"])?;
         Service("libvirtd").enable();
-        self
+        Ok(self)
     }
 
     fn configure_gdm(&mut self) -> &mut Self {
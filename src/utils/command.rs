@@ -3,7 +3,8 @@ use {
     std::{
         ffi::OsStr,
         io,
-        process::{self, Child, Stdio},
+        path::Path,
+        process::{self, Child, Output, Stdio},
     },
 };
 
@@ -30,6 +31,13 @@ impl Command {
         self
     }
 
+    /// Passes an argument which is logged as `***`.
+    pub(crate) fn secret_arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Command {
+        self.inner.arg(arg.as_ref());
+        self.args.push("***".to_owned());
+        self
+    }
+
     pub(crate) fn args<I, S>(&mut self, args: I) -> &mut Command
     where
         I: IntoIterator<Item = S>,
@@ -41,6 +49,25 @@ impl Command {
         self
     }
 
+    pub(crate) fn env<K, V>(&mut self, key: K, val: V) -> &mut Command
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.inner.env(key, val);
+        self
+    }
+
+    pub(crate) fn env_clear(&mut self) -> &mut Command {
+        self.inner.env_clear();
+        self
+    }
+
+    pub(crate) fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Command {
+        self.inner.current_dir(dir);
+        self
+    }
+
     pub(crate) fn stdin<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
         self.inner.stdin(cfg);
         self
@@ -51,8 +78,23 @@ impl Command {
         self
     }
 
+    pub(crate) fn stderr<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
+        self.inner.stderr(cfg);
+        self
+    }
+
+    /// Program and arguments as logged, with secrets hidden.
+    pub(crate) fn command_line(&self) -> String {
+        format!("{} {}", self.name, self.args.join(" "))
+    }
+
     pub(crate) fn spawn(&mut self) -> io::Result<Child> {
-        info!("{} {}", self.name, self.args.join(" "));
+        info!("{}", self.command_line());
         self.inner.spawn()
     }
+
+    pub(crate) fn output(&mut self) -> io::Result<Output> {
+        info!("{}", self.command_line());
+        self.inner.output()
+    }
 }
@@ -0,0 +1,102 @@
+use snafu::ensure;
+
+use {
+    crate::{
+        config::{Connectivity, ConnectivityMethod},
+        errors::{self, ALIResult},
+        utils::command::Command,
+    },
+    std::{
+        net::{SocketAddr, TcpStream, ToSocketAddrs},
+        process::Stdio,
+        time::Duration,
+    },
+};
+
+fn resolve(address: &str, port: u16) -> ALIResult<Vec<SocketAddr>> {
+    let addresses: Vec<_> = match (address, port).to_socket_addrs() {
+        Ok(addresses) => addresses.collect(),
+        Err(_) => vec![],
+    };
+
+    ensure!(
+        !addresses.is_empty(),
+        errors::DnsResolutionSnafu { host: address }
+    );
+    Ok(addresses)
+}
+
+fn ping(host: &str, timeout: u64) -> ALIResult<()> {
+    resolve(host, 0)?;
+
+    let status = Command::new("ping")
+        .args(&["-c", "1", "-W", &timeout.to_string(), host])
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap()
+        .wait()
+        .unwrap();
+
+    ensure!(
+        status.success(),
+        errors::NetworkUnreachableSnafu { target: host }
+    );
+    Ok(())
+}
+
+fn url_host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(&['/', ':'][..]).next().unwrap()
+}
+
+fn http(url: &str, timeout: u64) -> ALIResult<()> {
+    let status = Command::new("curl")
+        .args(&["--silent", "--fail", "--head", "--max-time"])
+        .arg(timeout.to_string())
+        .arg(url)
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap()
+        .wait()
+        .unwrap();
+
+    // Couldn't resolve host.
+    ensure!(
+        status.code() != Some(6),
+        errors::DnsResolutionSnafu {
+            host: url_host(url),
+        }
+    );
+    ensure!(
+        status.success(),
+        errors::NetworkUnreachableSnafu { target: url }
+    );
+    Ok(())
+}
+
+fn tcp(address: &str, timeout: u64) -> ALIResult<()> {
+    let (host, port) = address.rsplit_once(':').unwrap();
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let addresses = resolve(host, port.parse().unwrap())?;
+    let timeout = Duration::from_secs(timeout);
+
+    ensure!(
+        addresses
+            .iter()
+            .any(|address| TcpStream::connect_timeout(address, timeout).is_ok()),
+        errors::NetworkUnreachableSnafu { target: address }
+    );
+    Ok(())
+}
+
+/// Tells failed name resolution apart from unreachable hosts.
+pub(crate) fn check(connectivity: &Connectivity) -> ALIResult<()> {
+    let timeout = connectivity.timeout();
+
+    match connectivity.method() {
+        ConnectivityMethod::Ping => ping(connectivity.host(), timeout),
+        ConnectivityMethod::Http => http(connectivity.url(), timeout),
+        ConnectivityMethod::Tcp => tcp(connectivity.address(), timeout),
+        ConnectivityMethod::None => Ok(()),
+    }
+}
@@ -0,0 +1,60 @@
+use crate::utils::command::Command;
+
+#[derive(Debug)]
+pub(crate) struct BootEntry {
+    pub(crate) number: String,
+    pub(crate) label: String,
+}
+
+fn efibootmgr(args: &[&str]) -> String {
+    let output = Command::new("efibootmgr").args(args).output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Parses `efibootmgr` listing, e.g.:
+///
+/// ```text
+/// BootCurrent: 0001
+/// BootOrder: 0001,0000
+/// Boot0000* UEFI Shell    FvVol(...)
+/// Boot0001* GRUB    HD(1,GPT,...)/File(\EFI\GRUB\grubx64.efi)
+/// ```
+///
+/// with a tab between the label and the device path.
+pub(crate) fn boot_entries() -> Vec<BootEntry> {
+    efibootmgr(&[])
+        .lines()
+        .filter_map(|line| {
+            let line = line.strip_prefix("Boot")?;
+            let number = line.get(0..4)?;
+
+            if !number.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            let label = line[4..].trim_start_matches('*').trim_start();
+            let label = label.split('\t').next().unwrap().trim_end();
+
+            Some(BootEntry {
+                number: number.to_owned(),
+                label: label.to_owned(),
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn boot_order() -> Vec<String> {
+    efibootmgr(&[])
+        .lines()
+        .find_map(|line| line.strip_prefix("BootOrder:"))
+        .map(|order| order.trim().split(',').map(|n| n.to_owned()).collect())
+        .unwrap_or_default()
+}
+
+pub(crate) fn delete_entry(number: &str) {
+    efibootmgr(&["--bootnum", number, "--delete-bootnum"]);
+}
+
+pub(crate) fn set_boot_order(order: &[String]) {
+    efibootmgr(&["--bootorder", &order.join(",")]);
+}
@@ -0,0 +1,40 @@
+use std::path::{Path, PathBuf};
+
+pub(crate) const PROFILES_DIR: &str = "/var/lib/iwd";
+
+/// Path of the iwd profile of the network in `dir`.
+///
+/// iwd names profiles after the SSID, which is hex encoded when it contains
+/// anything besides alphanumerics, spaces, `_` and `-`.
+pub(crate) fn profile_path<P: AsRef<Path>>(dir: P, ssid: &str, secured: bool) -> PathBuf {
+    let plain = ssid
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '_' || c == '-');
+
+    let name = if plain {
+        ssid.to_owned()
+    } else {
+        let hex: String = ssid.bytes().map(|b| format!("{:02x}", b)).collect();
+        format!("={}", hex)
+    };
+    let kind = if secured { "psk" } else { "open" };
+
+    let mut path = dir.as_ref().to_path_buf();
+    path.push(format!("{}.{}", name, kind));
+    path
+}
+
+/// Contents of a profile, `passphrase` is empty for open networks.
+pub(crate) fn profile(passphrase: &str, hidden: bool) -> String {
+    let mut buffer = String::new();
+
+    if hidden {
+        buffer.push_str("[Settings]\nHidden=true\n\n");
+    }
+    if !passphrase.is_empty() {
+        buffer.push_str("[Security]\nPassphrase=");
+        buffer.push_str(passphrase);
+        buffer.push('\n');
+    }
+    buffer
+}
@@ -1,90 +1,91 @@
-use crate::errors;
+use crate::{constants::OFFLINE_PACMAN_CONF, errors};
 
 use {
     crate::errors::ALIResult,
     std::{
         env::{current_exe, var},
+        ffi::OsStr,
         fmt,
-        fs::File,
+        fs::{self, File},
         io::{prelude::*, stdin},
         path::{Path, PathBuf},
     },
 };
 
 pub(crate) mod command;
+pub(crate) mod connectivity;
+pub(crate) mod efibootmgr;
+pub(crate) mod iwd;
+pub(crate) mod package_check;
+pub(crate) mod pacman;
+pub(crate) mod pacman_conf;
+pub(crate) mod pacman_key;
 pub(crate) mod partitions;
+pub mod passwd;
+pub(crate) mod run_as;
+pub(crate) mod shell_conf;
+pub(crate) mod sudoers;
 
-use command::Command;
 use snafu::ensure;
+use {command::Command, run_as::RunAs};
 
-pub(crate) fn sudo_passwd_off(user: &str) {
-    let mut buffer = String::new();
+/// Installs the packages, from the offline repository when the installer
+/// ships its config.
+pub(crate) fn pacman_install(packages: &[&str]) -> ALIResult<()> {
+    if packages.is_empty() {
+        return Ok(());
+    }
+    let offline_conf = exe_dir().join(OFFLINE_PACMAN_CONF);
+    let config = Some(offline_conf.as_path()).filter(|path| path.exists());
+
+    pacman::run_with_retries(Path::new("/"), config, || {
+        let mut pacman = pacman::command(config);
+        pacman
+            .args(&["-S", "--noconfirm", "--needed"])
+            .args(packages);
+        pacman
+    })
+}
 
-    File::open("/etc/sudoers")
-        .unwrap()
-        .read_to_string(&mut buffer)
-        .unwrap();
+pub(crate) fn check_su() -> ALIResult<()> {
+    let user = var("USER");
 
-    buffer = buffer.replace(
-        &format!("\n{} ALL=(ALL) ALL", user),
-        &format!("\n{} ALL=(ALL) NOPASSWD: ALL", user),
+    ensure!(
+        matches!(user, Ok(user) if user == "root"),
+        errors::SudoRequiredSnafu
     );
-    File::create("/etc/sudoers")
-        .unwrap()
-        .write_all(buffer.as_bytes())
-        .unwrap();
+    Ok(())
 }
 
-pub(crate) fn sudo_passwd_on(user: &str) {
+/// Returns the amount of installed memory in bytes.
+pub(crate) fn mem_total() -> u64 {
     let mut buffer = String::new();
 
-    File::open("/etc/sudoers")
+    File::open("/proc/meminfo")
         .unwrap()
         .read_to_string(&mut buffer)
         .unwrap();
 
-    buffer = buffer.replace(
-        &format!("\n{} ALL=(ALL) NOPASSWD: ALL", user),
-        &format!("\n{} ALL=(ALL) ALL", user),
-    );
-    File::create("/etc/sudoers")
-        .unwrap()
-        .write_all(buffer.as_bytes())
-        .unwrap();
-}
-
-pub(crate) fn su_command(user: &str, command: &str, args: &[&str]) -> Command {
-    let mut eval_cmd = vec![command];
-    args.into_iter().for_each(|arg| eval_cmd.push(arg));
-    let eval_cmd = eval_cmd.join(" ");
-    let mut cmd = Command::new("su");
-    cmd.args(&["-c", &eval_cmd, user]);
-    cmd
-}
-
-pub(crate) fn pacman_install(packages: &[&str]) {
-    if packages.is_empty() {
-        return;
-    }
-    let status = Command::new("pacman")
-        .args(&["-S", "--noconfirm", "--needed"])
-        .args(packages)
-        .spawn()
-        .unwrap()
-        .wait()
+    // MemTotal:       16318508 kB
+    let kib: u64 = buffer
+        .lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))
+        .and_then(|value| value.trim().strip_suffix("kB"))
+        .map(|value| value.trim().parse().unwrap())
         .unwrap();
-
-    assert!(status.success());
+    kib * 1024
 }
 
-pub(crate) fn check_su() -> ALIResult<()> {
-    let user = var("USER");
+/// Returns the size of the block device in bytes.
+pub(crate) fn block_device_size<P: AsRef<Path>>(device: P) -> Option<u64> {
+    let device = fs::canonicalize(device).ok()?;
+    let mut path = PathBuf::from("/sys/class/block");
+    path.push(device.file_name()?);
+    path.push("size");
 
-    ensure!(
-        matches!(user, Ok(user) if user == "root"),
-        errors::SudoRequiredSnafu
-    );
-    Ok(())
+    let sectors = fs::read_to_string(path).ok()?;
+    let sectors: u64 = sectors.trim().parse().ok()?;
+    Some(sectors * 512)
 }
 
 pub fn exe_dir() -> PathBuf {
@@ -111,22 +112,12 @@ pub(crate) fn answer<P: AsRef<str>>(question: P) -> bool {
     }
 }
 
-pub(crate) fn git_clone<P: AsRef<Path>>(repo: &str, dir: P) -> ALIResult<()> {
-    let status = Command::new("git")
-        .args(&["clone", repo])
-        .arg(dir.as_ref())
-        .spawn()
-        .unwrap()
-        .wait()
-        .unwrap();
-
-    ensure!(
-        status.success(),
-        errors::CommandExecutionSnafu {
-            message: format!("git clone {} {:?}", repo, dir.as_ref()),
-        }
-    );
-    Ok(())
+/// Clones as the user, who then owns the working tree.
+pub(crate) fn git_clone<P: AsRef<Path>>(user: &RunAs, repo: &str, dir: P) -> ALIResult<()> {
+    user.run(
+        "git",
+        &[OsStr::new("clone"), repo.as_ref(), dir.as_ref().as_os_str()],
+    )
 }
 
 pub(crate) struct Service<'a>(pub(crate) &'a str);
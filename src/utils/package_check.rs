@@ -0,0 +1,178 @@
+use snafu::{ensure, ResultExt};
+
+use {
+    crate::{
+        errors::{self, ALIResult},
+        utils::{command::Command, pacman},
+    },
+    serde::Deserialize,
+    std::{
+        fs,
+        path::{Path, PathBuf},
+        process::Stdio,
+    },
+};
+
+/// Directory for the databases and the config of the check, removed on drop
+/// also when the check fails.
+pub(crate) struct WorkDir(PathBuf);
+
+impl WorkDir {
+    /// Replaces what a previous run left behind.
+    pub(crate) fn new<P: AsRef<Path>>(path: P) -> WorkDir {
+        let path = path.as_ref().to_path_buf();
+
+        if path.exists() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        fs::create_dir_all(&path).unwrap();
+        WorkDir(path)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        if let Err(error) = fs::remove_dir_all(&self.0) {
+            eprintln!("Unable to remove {:?}: {}", self.0, error);
+        }
+    }
+}
+
+fn pacman(config: &Path, dbpath: &Path) -> Command {
+    let mut pacman = pacman::command(Some(config));
+    pacman.arg("--dbpath").arg(dbpath);
+    pacman
+}
+
+/// Downloads the sync databases to `dbpath`, leaving the ones of the live
+/// system alone.
+pub(crate) fn sync(config: &Path, dbpath: &Path) -> ALIResult<()> {
+    let mut pacman = pacman(config, dbpath);
+    let status = pacman
+        .arg("-Sy")
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap()
+        .wait()
+        .unwrap();
+
+    ensure!(
+        status.success(),
+        errors::CommandExecutionSnafu {
+            message: pacman.command_line(),
+        }
+    );
+    Ok(())
+}
+
+/// Names of `packages` which are neither packages nor groups in the
+/// repositories of `config`, `sync` must run first.
+///
+/// The packages are resolved together as they're installed together, pacman
+/// reports every name it can't find before failing.
+pub(crate) fn unknown_in_repos(
+    config: &Path,
+    dbpath: &Path,
+    packages: &[&str],
+) -> ALIResult<Vec<String>> {
+    if packages.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut pacman_print = pacman(config, dbpath);
+    let output = pacman_print
+        .args(&["-Sp", "--noconfirm", "--print-format", "%n"])
+        .args(packages)
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let not_found: Vec<_> = stderr
+        .lines()
+        .filter_map(|line| line.strip_prefix("error: target not found: "))
+        .collect();
+
+    ensure!(
+        output.status.success() || !not_found.is_empty(),
+        errors::CommandExecutionSnafu {
+            message: pacman_print.command_line(),
+        }
+    );
+    let mut unknown = vec![];
+
+    for name in not_found {
+        let status = pacman(config, dbpath)
+            .args(&["-Sg", name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap()
+            .wait()
+            .unwrap();
+
+        if !status.success() {
+            unknown.push(name.to_owned());
+        }
+    }
+    Ok(unknown)
+}
+
+/// Info response of the AUR RPC, e.g.
+///
+/// ```text
+/// {"resultcount":1,"results":[{"ID":1234,"Name":"yay",...}],"type":"multiinfo","version":5}
+/// ```
+#[derive(Deserialize)]
+struct Response {
+    results: Vec<Pkg>,
+}
+
+#[derive(Deserialize)]
+struct Pkg {
+    #[serde(rename = "Name")]
+    name: String,
+}
+
+/// Names of `packages` missing from the AUR, asked with a single `info`
+/// query to `rpc`.
+pub(crate) fn unknown_in_aur(rpc: &str, packages: &[&str]) -> ALIResult<Vec<String>> {
+    if packages.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut curl = Command::new("curl");
+    curl.args(&[
+        "--silent",
+        "--fail",
+        "--get",
+        "--data",
+        "v=5",
+        "--data",
+        "type=info",
+    ])
+    .arg(rpc);
+
+    for package in packages {
+        curl.arg("--data-urlencode")
+            .arg(format!("arg[]={}", package));
+    }
+    let output = curl.output().unwrap();
+
+    ensure!(
+        output.status.success(),
+        errors::CommandExecutionSnafu {
+            message: curl.command_line(),
+        }
+    );
+    let response: Response =
+        serde_json::from_slice(&output.stdout).context(errors::AurResponseSnafu { rpc })?;
+    let found: Vec<_> = response.results.iter().map(|pkg| &*pkg.name).collect();
+
+    Ok(packages
+        .iter()
+        .filter(|package| !found.contains(package))
+        .map(|package| package.to_string())
+        .collect())
+}
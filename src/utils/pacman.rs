@@ -0,0 +1,349 @@
+use snafu::ensure;
+
+use {
+    crate::{
+        errors::{self, ALIResult},
+        utils::{command::Command, pacman_key::pacman_key},
+    },
+    std::{
+        fs,
+        io::{self, BufRead, BufReader, Read, Write},
+        path::Path,
+        process::Stdio,
+        thread,
+    },
+};
+
+const ATTEMPTS: u32 = 3;
+
+/// Why a transaction failed, judged by the output of pacman.
+#[derive(Debug, PartialEq)]
+enum Failure {
+    /// Invalid signature or a key missing from the keyring.
+    Signature,
+    /// Files owned by no package are in the way, e.g.
+    /// `linux: /boot/vmlinuz-linux exists in filesystem`.
+    FileConflict(Vec<String>),
+    PackageNotFound(Vec<String>),
+    /// Names of the package files in the cache, empty when pacman removed
+    /// them already.
+    Corrupted(Vec<String>),
+    DbSync,
+    Unknown,
+}
+
+const SIGNATURE_ERRORS: &[&str] = &[
+    "signature from",
+    "pgp signature",
+    "unknown trust",
+    "could not be looked up remotely",
+    "required key missing from keyring",
+];
+
+const DB_SYNC_ERRORS: &[&str] = &[
+    "failed to synchronize all databases",
+    "failed to update",
+    "failed retrieving file",
+    "database file for",
+];
+
+fn classify(output: &str) -> Failure {
+    let lines = || output.lines().map(|line| line.trim());
+    let contains_any = |patterns: &[&str]| {
+        lines().any(|line| {
+            let line = line.to_lowercase();
+            patterns.iter().any(|pattern| line.contains(pattern))
+        })
+    };
+
+    let not_found: Vec<_> = lines()
+        .filter_map(|line| line.strip_prefix("error: target not found: "))
+        .map(|package| package.to_owned())
+        .collect();
+
+    if !not_found.is_empty() {
+        return Failure::PackageNotFound(not_found);
+    }
+    let conflicts: Vec<_> = lines()
+        .filter_map(|line| line.split_once(" exists in filesystem"))
+        .map(|(file, _)| file.to_owned())
+        .collect();
+
+    if !conflicts.is_empty() {
+        return Failure::FileConflict(conflicts);
+    }
+    if contains_any(SIGNATURE_ERRORS) {
+        return Failure::Signature;
+    }
+    // :: File /var/cache/pacman/pkg/zstd-1.5.5-1-x86_64.pkg.tar.zst is corrupted
+    // (invalid or corrupted package (checksum)).
+    let corrupted: Vec<_> = lines()
+        .map(|line| line.trim_start_matches(":: "))
+        .filter_map(|line| line.strip_prefix("File "))
+        .filter_map(|line| line.split_once(" is corrupted"))
+        .filter_map(|(path, _)| Path::new(path).file_name()?.to_str())
+        .map(|file| file.to_owned())
+        .collect();
+
+    if !corrupted.is_empty() || contains_any(&["invalid or corrupted package"]) {
+        return Failure::Corrupted(corrupted);
+    }
+    if contains_any(DB_SYNC_ERRORS) {
+        return Failure::DbSync;
+    }
+    Failure::Unknown
+}
+
+/// Shows the output while keeping it for `classify`.
+fn tee<R: Read, W: Write>(reader: R, mut writer: W) -> String {
+    let mut output = String::new();
+
+    for line in BufReader::new(reader).split(b'\n') {
+        let line = line.unwrap();
+        let line = String::from_utf8_lossy(&line);
+        writeln!(writer, "{}", line).unwrap();
+        output.push_str(&line);
+        output.push('\n');
+    }
+    output
+}
+
+fn run_captured(command: &mut Command) -> (bool, String) {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let stderr = child.stderr.take().unwrap();
+    let errors = thread::spawn(move || tee(stderr, io::stderr()));
+    let mut output = tee(child.stdout.take().unwrap(), io::stdout());
+    output.push_str(&errors.join().unwrap());
+
+    (child.wait().unwrap().success(), output)
+}
+
+fn run(command: &mut Command) -> ALIResult<()> {
+    let status = command.spawn().unwrap().wait().unwrap();
+
+    ensure!(
+        status.success(),
+        errors::CommandExecutionSnafu {
+            message: command.command_line(),
+        }
+    );
+    Ok(())
+}
+
+/// `pacman` with the given config, the default one otherwise.
+pub(crate) fn command(config: Option<&Path>) -> Command {
+    let mut pacman = Command::new("pacman");
+
+    if let Some(config) = config {
+        pacman.arg("--config").arg(config);
+    }
+    pacman
+}
+
+/// Keys are checked against the keyring of the live system, also when
+/// installing to `/mnt`.
+fn refresh_keyring(config: Option<&Path>) -> ALIResult<()> {
+    pacman_key(None, &["--populate"])?;
+    run(command(config).args(&["-Sy", "--noconfirm", "archlinux-keyring"]))
+}
+
+fn sync_databases(config: Option<&Path>) -> ALIResult<()> {
+    run(command(config).arg("-Syy"))
+}
+
+fn remove_cached(root: &Path, files: &[String]) {
+    let cache = root.join("var/cache/pacman/pkg");
+
+    for file in files {
+        // Gone already if pacman removed it.
+        let _ = fs::remove_file(cache.join(file));
+        let _ = fs::remove_file(cache.join(format!("{}.sig", file)));
+    }
+}
+
+/// Runs the transaction built by `transaction` until it succeeds, fixing
+/// what made it fail in between. `root` is where the packages go, `/mnt`
+/// for pacstrap.
+pub(crate) fn run_with_retries<F>(
+    root: &Path,
+    config: Option<&Path>,
+    mut transaction: F,
+) -> ALIResult<()>
+where
+    F: FnMut() -> Command,
+{
+    let mut attempt = 1;
+
+    loop {
+        let mut command = transaction();
+        let (success, output) = run_captured(&mut command);
+
+        if success {
+            return Ok(());
+        }
+        let failure = classify(&output);
+
+        match &failure {
+            Failure::PackageNotFound(packages) => {
+                return errors::PackageNotFoundSnafu {
+                    packages: packages.clone(),
+                }
+                .fail();
+            }
+            Failure::FileConflict(files) => {
+                return errors::PackageFileConflictSnafu {
+                    files: files.clone(),
+                }
+                .fail();
+            }
+            _ => (),
+        }
+        // Retrying won't help without knowing what went wrong.
+        ensure!(
+            attempt < ATTEMPTS && failure != Failure::Unknown,
+            errors::PacmanFailedSnafu {
+                command: command.command_line(),
+                attempts: attempt,
+            }
+        );
+        eprintln!(
+            "{} failed ({:?}), attempt {} of {}",
+            command.command_line(),
+            failure,
+            attempt + 1,
+            ATTEMPTS
+        );
+        // The next attempt shows whether the fix helped, a transient mirror
+        // error shouldn't use up the remaining ones.
+        let fixed = match failure {
+            Failure::Signature => refresh_keyring(config),
+            Failure::Corrupted(files) => {
+                remove_cached(root, &files);
+                Ok(())
+            }
+            // pacstrap syncs the databases of the target itself.
+            Failure::DbSync if root == Path::new("/") => sync_databases(config),
+            _ => Ok(()),
+        };
+        if let Err(error) = fixed {
+            eprintln!("{}", error);
+        }
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_unknown_trust() {
+        let output = "\
+(1/1) checking keys in keyring
+(1/1) checking package integrity
+error: linux: signature from \"Jan Alexander Steffens (heftig) <heftig@archlinux.org>\" is unknown trust
+:: File /var/cache/pacman/pkg/linux-6.5.3.arch1-1-x86_64.pkg.tar.zst is corrupted (invalid or corrupted package (PGP signature)).
+Do you want to delete it? [Y/n]
+error: failed to commit transaction (invalid or corrupted package)
+Errors occurred, no packages were upgraded.
+";
+        assert_eq!(classify(output), Failure::Signature);
+    }
+
+    #[test]
+    fn classifies_missing_key() {
+        let output = "\
+(1/1) checking keys in keyring
+downloading required keys...
+:: Import PGP key 3B94A80E50A477C7, \"Jan Alexander Steffens (heftig) <heftig@archlinux.org>\"? [Y/n]
+error: key \"3B94A80E50A477C7\" could not be looked up remotely
+error: required key missing from keyring
+error: failed to commit transaction (unexpected error)
+Errors occurred, no packages were upgraded.
+";
+        assert_eq!(classify(output), Failure::Signature);
+    }
+
+    #[test]
+    fn classifies_file_conflict() {
+        let output = "\
+(1/1) checking for file conflicts
+error: failed to commit transaction (conflicting files)
+nvidia-utils: /usr/lib/libEGL.so exists in filesystem (owned by libglvnd)
+nvidia-utils: /usr/lib/libGLESv2.so exists in filesystem
+Errors occurred, no packages were upgraded.
+";
+        assert_eq!(
+            classify(output),
+            Failure::FileConflict(vec![
+                "nvidia-utils: /usr/lib/libEGL.so".to_owned(),
+                "nvidia-utils: /usr/lib/libGLESv2.so".to_owned(),
+            ])
+        );
+    }
+
+    #[test]
+    fn classifies_package_not_found() {
+        let output = "\
+error: target not found: fierfox
+error: target not found: linux-lst
+";
+        assert_eq!(
+            classify(output),
+            Failure::PackageNotFound(vec!["fierfox".to_owned(), "linux-lst".to_owned()])
+        );
+    }
+
+    #[test]
+    fn classifies_corrupted_package() {
+        let output = "\
+(1/1) checking package integrity
+:: File /var/cache/pacman/pkg/zstd-1.5.5-1-x86_64.pkg.tar.zst is corrupted (invalid or corrupted package (checksum)).
+Do you want to delete it? [Y/n]
+error: failed to commit transaction (invalid or corrupted package (checksum))
+Errors occurred, no packages were upgraded.
+";
+        assert_eq!(
+            classify(output),
+            Failure::Corrupted(vec!["zstd-1.5.5-1-x86_64.pkg.tar.zst".to_owned()])
+        );
+    }
+
+    #[test]
+    fn classifies_failed_sync() {
+        let output = "\
+:: Synchronizing package databases...
+error: failed retrieving file 'core.db' from mirror.example.org : Could not resolve host: mirror.example.org
+error: failed to update core (download library error)
+error: failed to synchronize all databases (download library error)
+";
+        assert_eq!(classify(output), Failure::DbSync);
+    }
+
+    #[test]
+    fn classifies_outdated_database() {
+        let output = "\
+error: failed retrieving file 'linux-6.5.2.arch1-1-x86_64.pkg.tar.zst' from geo.mirror.pkgbuild.com : The requested URL returned error: 404
+warning: failed to retrieve some files
+error: failed to commit transaction (failed to retrieve some files)
+Errors occurred, no packages were upgraded.
+";
+        assert_eq!(classify(output), Failure::DbSync);
+    }
+
+    #[test]
+    fn leaves_other_errors_unknown() {
+        let output = "\
+error: failed to init transaction (unable to lock database)
+error: could not lock database: File exists
+  if you're sure a package manager is not already
+  running, you can remove /var/lib/pacman/db.lck
+";
+        assert_eq!(classify(output), Failure::Unknown);
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+fn section_name(line: &str) -> Option<&str> {
+    let line = line.trim();
+    line.strip_prefix('[')?.strip_suffix(']')
+}
+
+/// Returns `Include = ...` of `#Include = ...`, but not plain comments.
+fn commented_option(line: &str) -> Option<&str> {
+    let option = line.trim_start().strip_prefix('#')?;
+    let (key, _) = option.split_once('=')?;
+    let key = key.trim();
+
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    Some(option)
+}
+
+/// Editor for `/etc/pacman.conf` that keeps comments and untouched lines.
+#[derive(Debug)]
+pub(crate) struct PacmanConf {
+    path: PathBuf,
+    lines: Vec<String>,
+}
+
+impl PacmanConf {
+    pub(crate) fn open<P: AsRef<Path>>(path: P) -> PacmanConf {
+        let path = path.as_ref().to_path_buf();
+        let buffer = fs::read_to_string(&path).unwrap();
+        let lines = buffer.lines().map(|line| line.to_owned()).collect();
+        PacmanConf { path, lines }
+    }
+
+    /// Line range of the section body, without the header.
+    fn section(&self, name: &str) -> Option<(usize, usize)> {
+        let header = self
+            .lines
+            .iter()
+            .position(|line| section_name(line) == Some(name))?;
+
+        let end = self.lines[header + 1..]
+            .iter()
+            .position(|line| {
+                let line = line.trim_start_matches('#');
+                section_name(line).is_some()
+            })
+            .map(|offset| header + 1 + offset)
+            .unwrap_or(self.lines.len());
+
+        Some((header + 1, end))
+    }
+
+    fn option_key(line: &str) -> &str {
+        line.split('=').next().unwrap().trim()
+    }
+
+    /// Sets an option of `[options]`, `None` stands for a flag like `Color`.
+    ///
+    /// Replaces the active line or the commented default, e.g.
+    /// `#ParallelDownloads = 5`, otherwise adds a line.
+    pub(crate) fn set_option(&mut self, key: &str, value: Option<&str>) {
+        let line = match value {
+            Some(value) => format!("{} = {}", key, value),
+            None => key.to_owned(),
+        };
+        let (start, end) = self.section("options").unwrap();
+
+        let active = (start..end).find(|&i| Self::option_key(&self.lines[i]) == key);
+        let commented = (start..end).find(|&i| {
+            let line = self.lines[i].trim_start();
+            matches!(line.strip_prefix('#'), Some(line) if Self::option_key(line) == key)
+        });
+
+        match active.or(commented) {
+            Some(index) => self.lines[index] = line,
+            None => {
+                // After the last option, the section ends with comments
+                // describing the repositories.
+                let index = (start..end)
+                    .rev()
+                    .find(|&i| {
+                        let line = self.lines[i].trim();
+                        !line.is_empty() && !line.starts_with('#')
+                    })
+                    .map_or(start, |i| i + 1);
+                self.lines.insert(index, line);
+            }
+        }
+    }
+
+    /// Uncomments a repository shipped commented out, such as `[multilib]`.
+    pub(crate) fn enable_repo(&mut self, name: &str) {
+        if self.section(name).is_some() {
+            return;
+        }
+        let commented = format!("#[{}]", name);
+        let header = self.lines.iter().position(|line| line.trim() == commented);
+
+        match header {
+            Some(header) => {
+                self.lines[header] = format!("[{}]", name);
+
+                for line in &mut self.lines[header + 1..] {
+                    match commented_option(line) {
+                        Some(option) => *line = option.to_owned(),
+                        None => break,
+                    }
+                }
+            }
+            None => self.add_repo(name, None, &[]),
+        }
+    }
+
+    /// Adds the repository at the end, replacing the one with the same name.
+    /// Without servers it uses the mirrorlist.
+    pub(crate) fn add_repo(&mut self, name: &str, sig_level: Option<&str>, servers: &[String]) {
+        if let Some((start, end)) = self.section(name) {
+            self.lines.drain(start - 1..end);
+        }
+        while matches!(self.lines.last(), Some(line) if line.trim().is_empty()) {
+            self.lines.pop();
+        }
+        self.lines.push(String::new());
+        self.lines.push(format!("[{}]", name));
+
+        if let Some(sig_level) = sig_level {
+            self.lines.push(format!("SigLevel = {}", sig_level));
+        }
+        if servers.is_empty() {
+            self.lines
+                .push("Include = /etc/pacman.d/mirrorlist".to_owned());
+        }
+        for server in servers {
+            self.lines.push(format!("Server = {}", server));
+        }
+    }
+
+    /// Sets `SigLevel` of `[options]` and of every repository, where it
+    /// would override the global one.
+    pub(crate) fn set_sig_level(&mut self, sig_level: &str) {
+        let mut lines = Vec::with_capacity(self.lines.len());
+        let mut repo = false;
+
+        for line in self.lines.drain(..) {
+            if let Some(name) = section_name(&line) {
+                repo = name != "options";
+                lines.push(line);
+
+                if repo {
+                    lines.push(format!("SigLevel = {}", sig_level));
+                }
+                continue;
+            }
+            if !(repo && Self::option_key(&line) == "SigLevel") {
+                lines.push(line);
+            }
+        }
+        self.lines = lines;
+        self.set_option("SigLevel", Some(sig_level));
+    }
+
+    /// Points the repositories including the mirrorlist at another one.
+    pub(crate) fn set_mirrorlist<P: AsRef<Path>>(&mut self, mirrorlist: P) {
+        let include = format!("Include = {}", mirrorlist.as_ref().display());
+
+        for line in &mut self.lines {
+            let option = line.trim();
+
+            if option.starts_with("Include") && option.ends_with("/etc/pacman.d/mirrorlist") {
+                *line = include.clone();
+            }
+        }
+    }
+
+    pub(crate) fn save(&self) {
+        let mut buffer = self.lines.join("\n");
+        buffer.push('\n');
+        fs::write(&self.path, buffer).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+use snafu::ensure;
+
+use {
+    crate::{
+        errors::{self, ALIResult},
+        utils::command::Command,
+    },
+    std::{ffi::OsStr, path::Path},
+};
+
+/// Runs `pacman-key` on the keyring in `gpgdir`, the live one by default.
+pub(crate) fn pacman_key<S: AsRef<OsStr>>(gpgdir: Option<&Path>, args: &[S]) -> ALIResult<()> {
+    let mut pacman_key = Command::new("pacman-key");
+
+    if let Some(gpgdir) = gpgdir {
+        pacman_key.arg("--gpgdir").arg(gpgdir);
+    }
+    let status = pacman_key.args(args).spawn().unwrap().wait().unwrap();
+
+    ensure!(
+        status.success(),
+        errors::CommandExecutionSnafu {
+            message: format!(
+                "pacman-key {}",
+                args.iter()
+                    .map(|arg| arg.as_ref().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        }
+    );
+    Ok(())
+}
+
+/// Fingerprints of the primary keys in the file, e.g.
+///
+/// ```text
+/// pub:-:4096:1:786C63F330D7CB92:1447860582:::-:::scSC::::::23::0:
+/// fpr:::::::::ABAF11C65A2970B130ABE3C479BE3E4300411886:
+/// ```
+pub(crate) fn fingerprints(key_file: &Path) -> ALIResult<Vec<String>> {
+    let output = Command::new("gpg")
+        .args(&["--with-colons", "--import-options", "show-only", "--import"])
+        .arg(key_file)
+        .output()
+        .unwrap();
+
+    ensure!(
+        output.status.success(),
+        errors::CommandExecutionSnafu {
+            message: format!("gpg --import {:?}", key_file),
+        }
+    );
+    let output = String::from_utf8(output.stdout).unwrap();
+    let mut fingerprints = vec![];
+    let mut primary = false;
+
+    for line in output.lines() {
+        let fields: Vec<_> = line.split(':').collect();
+
+        match fields[0] {
+            "pub" => primary = true,
+            "fpr" if primary => {
+                fingerprints.push(fields[9].to_owned());
+                primary = false;
+            }
+            _ => (),
+        }
+    }
+    Ok(fingerprints)
+}
+
+/// Adds the key from the file or fetches it by fingerprint from the
+/// keyserver, then signs it locally so pacman trusts it.
+pub(crate) fn import_key(
+    gpgdir: Option<&Path>,
+    key_file: Option<&Path>,
+    key_id: Option<&str>,
+) -> ALIResult<()> {
+    let fingerprints = match (key_file, key_id) {
+        (Some(key_file), key_id) => {
+            pacman_key(gpgdir, &[OsStr::new("--add"), key_file.as_os_str()])?;
+
+            match key_id {
+                Some(key_id) => vec![key_id.to_owned()],
+                None => fingerprints(key_file)?,
+            }
+        }
+        (None, Some(key_id)) => {
+            pacman_key(gpgdir, &["--recv-keys", key_id])?;
+            vec![key_id.to_owned()]
+        }
+        (None, None) => return Ok(()),
+    };
+    for fingerprint in fingerprints {
+        pacman_key(gpgdir, &["--lsign-key", &fingerprint])?;
+    }
+    Ok(())
+}
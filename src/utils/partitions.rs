@@ -12,12 +12,14 @@ pub(crate) struct Partitions {
     drive: PathBuf,
     efi: Option<PathBuf>,
     boot: Option<PathBuf>,
+    swap: Option<PathBuf>,
     root: Option<PathBuf>,
 }
 
 impl Partitions {
-    pub(crate) fn new(drive: PathBuf, root: &str, boot: &str, efi: &str) -> Self {
+    pub(crate) fn new(drive: PathBuf, root: &str, swap: &str, boot: &str, efi: &str) -> Self {
         let root = Partitions::str_to_path(root);
+        let swap = Partitions::str_to_path(swap);
         let boot = Partitions::str_to_path(boot);
         let efi = Partitions::str_to_path(efi);
 
@@ -25,6 +27,7 @@ impl Partitions {
         Partitions {
             drive,
             root,
+            swap,
             efi,
             boot,
         }
@@ -83,6 +86,10 @@ impl Partitions {
                 self.boot = Some(PathBuf::from(path));
                 return;
             }
+            if label == "Arch Linux swap partition" {
+                self.swap = Some(PathBuf::from(path));
+                return;
+            }
             if label == "Arch Linux root partition" {
                 self.root = Some(PathBuf::from(path));
                 return;
@@ -110,6 +117,10 @@ impl Partitions {
         self.boot.as_ref()
     }
 
+    pub(crate) fn swap(&self) -> Option<&PathBuf> {
+        self.swap.as_ref()
+    }
+
     pub(crate) fn root(&self) -> Option<&PathBuf> {
         self.root.as_ref()
     }
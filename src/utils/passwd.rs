@@ -0,0 +1,123 @@
+use snafu::ensure;
+
+use {
+    crate::{
+        errors::{self, ALIResult},
+        utils::command::Command,
+    },
+    std::{
+        io::{prelude::*, stdin, stdout},
+        process::Stdio,
+        str::FromStr,
+    },
+};
+
+#[derive(Debug, Clone, Copy)]
+pub enum HashMethod {
+    Yescrypt,
+    Sha512,
+}
+
+impl FromStr for HashMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "yescrypt" => Ok(HashMethod::Yescrypt),
+            "sha-512" | "sha512" => Ok(HashMethod::Sha512),
+            _ => Err(format!("unknown hash method: {}", s)),
+        }
+    }
+}
+
+/// Checks the hash is in crypt(3) format accepted by `chpasswd -e`.
+pub(crate) fn is_password_hash(hash: &str) -> bool {
+    let id = match hash
+        .strip_prefix('$')
+        .and_then(|hash| hash.split('$').next())
+    {
+        Some(id) => id,
+        None => return false,
+    };
+    matches!(id, "y" | "6") && hash.split('$').count() >= 4
+}
+
+fn set_echo(enabled: bool) {
+    let mode = if enabled { "echo" } else { "-echo" };
+
+    // Not a terminal when the input is piped, nothing to hide then.
+    let _ = Command::new("stty")
+        .arg(mode)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::null())
+        .output();
+}
+
+/// Reads a line from stdin without echoing it.
+pub(crate) fn read_password(prompt: &str) -> String {
+    print!("{}: ", prompt);
+    stdout().flush().unwrap();
+
+    let mut password = String::new();
+    set_echo(false);
+    let result = stdin().read_line(&mut password);
+    set_echo(true);
+    println!();
+
+    result.unwrap();
+    password.trim_end_matches(&['\r', '\n'][..]).to_owned()
+}
+
+/// Reads a new password, asking to type it twice.
+pub(crate) fn read_new_password(prompt: &str) -> ALIResult<String> {
+    let password = read_password(prompt);
+    let confirmation = read_password("Retype the password");
+
+    ensure!(password == confirmation, errors::PasswordMismatchSnafu);
+    ensure!(!password.is_empty(), errors::PasswordEmptySnafu);
+    Ok(password)
+}
+
+pub(crate) fn hash_password(password: &str, method: HashMethod) -> ALIResult<String> {
+    let mut command = match method {
+        HashMethod::Sha512 => {
+            let mut command = Command::new("openssl");
+            command.args(&["passwd", "-6", "-stdin"]);
+            command
+        }
+        // Provided by the whois package.
+        HashMethod::Yescrypt => {
+            let mut command = Command::new("mkpasswd");
+            command.args(&["--method=yescrypt", "--stdin"]);
+            command
+        }
+    };
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .as_mut()
+        .unwrap()
+        .write_all(format!("{}\n", password).as_bytes())
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+
+    ensure!(
+        output.status.success(),
+        errors::CommandExecutionSnafu {
+            message: format!("hash password with {:?}", method),
+        }
+    );
+    Ok(String::from_utf8(output.stdout).unwrap().trim().to_owned())
+}
+
+/// Asks for a password and returns its hash to put into the config.
+pub fn mkpasswd(method: HashMethod) -> ALIResult<String> {
+    let password = read_new_password("Password")?;
+    hash_password(&password, method)
+}
@@ -0,0 +1,112 @@
+use snafu::{ensure, OptionExt, ResultExt};
+
+use {
+    crate::{
+        errors::{self, ALIResult},
+        utils::command::Command,
+    },
+    std::{
+        env,
+        ffi::OsStr,
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Login `PATH` of Arch, `/etc/profile` with `perlbin.sh`.
+const PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/bin:\
+                    /usr/bin/site_perl:/usr/bin/vendor_perl:/usr/bin/core_perl";
+
+/// Passes a command to a user, as opposed to `su -c` no shell parses the
+/// arguments.
+pub(crate) struct RunAs {
+    name: String,
+    home: PathBuf,
+    shell: String,
+}
+
+impl RunAs {
+    /// Looks the user up in `/etc/passwd`, e.g.
+    /// `user:x:1000:1000::/home/user:/bin/bash`.
+    pub(crate) fn new(user: &str) -> ALIResult<RunAs> {
+        let passwd = fs::read_to_string("/etc/passwd").context(errors::PasswdReadSnafu)?;
+
+        let fields: Vec<_> = passwd
+            .lines()
+            .map(|line| line.split(':').collect::<Vec<_>>())
+            .find(|fields| fields.len() == 7 && fields[0] == user)
+            .context(errors::UserNotFoundSnafu { user })?;
+
+        Ok(RunAs {
+            name: user.to_owned(),
+            home: PathBuf::from(fields[5]),
+            shell: fields[6].to_owned(),
+        })
+    }
+
+    pub(crate) fn home(&self) -> &Path {
+        &self.home
+    }
+
+    /// Prepares `runuser -u <user> -- <program>` with the environment of the
+    /// user and the home as working directory.
+    pub(crate) fn command<S: AsRef<OsStr>>(&self, program: S) -> Command {
+        let mut command = Command::new("runuser");
+        command
+            .args(&["-u", &self.name, "--"])
+            .arg(program)
+            .env_clear()
+            .env("HOME", &self.home)
+            .env("USER", &self.name)
+            .env("LOGNAME", &self.name)
+            .env("SHELL", &self.shell)
+            .env("PATH", PATH)
+            .env("XDG_CONFIG_HOME", self.home.join(".config"))
+            .env("XDG_CACHE_HOME", self.home.join(".cache"))
+            .env("XDG_DATA_HOME", self.home.join(".local/share"))
+            .current_dir(&self.home);
+
+        for var in &["LANG", "TERM"] {
+            if let Ok(value) = env::var(var) {
+                command.env(var, value);
+            }
+        }
+        command
+    }
+
+    /// Runs the program in `dir` and waits for it to succeed.
+    pub(crate) fn run_in<P, S>(&self, dir: P, program: &str, args: &[S]) -> ALIResult<()>
+    where
+        P: AsRef<Path>,
+        S: AsRef<OsStr>,
+    {
+        let status = self
+            .command(program)
+            .args(args)
+            .current_dir(dir)
+            .spawn()
+            .context(errors::RunAsSpawnSnafu {
+                user: &self.name,
+                program,
+            })?
+            .wait()
+            .context(errors::RunAsSpawnSnafu {
+                user: &self.name,
+                program,
+            })?;
+
+        ensure!(
+            status.success(),
+            errors::RunAsFailedSnafu {
+                user: &self.name,
+                program,
+                code: status.code(),
+            }
+        );
+        Ok(())
+    }
+
+    pub(crate) fn run<S: AsRef<OsStr>>(&self, program: &str, args: &[S]) -> ALIResult<()> {
+        self.run_in(&self.home, program, args)
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    fs::File,
+    io::prelude::*,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+/// Editor for configuration files written in shell variable syntax, such as
+/// `/etc/mkinitcpio.conf` or `/etc/default/grub`.
+///
+/// Only the touched assignments are rewritten, comments and the rest of the
+/// file are kept as is.
+#[derive(Debug)]
+pub(crate) struct ShellConf {
+    path: PathBuf,
+    lines: Vec<String>,
+}
+
+impl ShellConf {
+    pub(crate) fn open<P: AsRef<Path>>(path: P) -> ShellConf {
+        let path = path.as_ref().to_path_buf();
+        let mut buffer = String::new();
+
+        if path.exists() {
+            File::open(&path)
+                .unwrap()
+                .read_to_string(&mut buffer)
+                .unwrap();
+        }
+        let lines = buffer.lines().map(|line| line.to_owned()).collect();
+        ShellConf { path, lines }
+    }
+
+    /// Finds the lines of an active assignment, arrays may span several lines.
+    fn find(&self, key: &str) -> Option<Range<usize>> {
+        let prefix = format!("{}=", key);
+        let start = self
+            .lines
+            .iter()
+            .position(|line| line.trim_start().starts_with(&prefix))?;
+
+        let value = self.lines[start].trim_start()[prefix.len()..].trim();
+
+        if !value.starts_with('(') || value.contains(')') {
+            return Some(start..start + 1);
+        }
+        let end = self.lines[start + 1..]
+            .iter()
+            .position(|line| line.contains(')'))
+            .map(|offset| start + 1 + offset)
+            .unwrap_or(self.lines.len() - 1);
+
+        Some(start..end + 1)
+    }
+
+    /// Finds a commented out assignment, e.g. `#COMPRESSION="zstd"`.
+    fn find_commented(&self, key: &str) -> Option<usize> {
+        let prefix = format!("{}=", key);
+
+        self.lines.iter().position(|line| {
+            let line = line.trim_start();
+            line.starts_with('#') && line[1..].trim_start().starts_with(&prefix)
+        })
+    }
+
+    fn raw_value(&self, key: &str) -> Option<String> {
+        let range = self.find(key)?;
+        let value = self.lines[range].join("\n");
+        let (_, value) = value.split_once('=').unwrap();
+        Some(value.trim().to_owned())
+    }
+
+    fn assign(&mut self, key: &str, value: String) {
+        let line = format!("{}={}", key, value);
+
+        if let Some(range) = self.find(key) {
+            self.lines.splice(range, vec![line]);
+        } else if let Some(index) = self.find_commented(key) {
+            self.lines.insert(index + 1, line);
+        } else {
+            self.lines.push(line);
+        }
+    }
+
+    pub(crate) fn set_value(&mut self, key: &str, value: &str) {
+        self.assign(key, format!("\"{}\"", value));
+    }
+
+    pub(crate) fn array(&self, key: &str) -> Option<Vec<String>> {
+        let value = self.raw_value(key)?;
+        let value = value.trim_start_matches('(').trim_end_matches(')');
+
+        let items = value
+            .split_whitespace()
+            .map(|item| item.trim_matches(|c| c == '"' || c == '\'').to_owned())
+            .collect();
+        Some(items)
+    }
+
+    pub(crate) fn set_array<S: AsRef<str>>(&mut self, key: &str, items: &[S]) {
+        let items: Vec<&str> = items.iter().map(|item| item.as_ref()).collect();
+        self.assign(key, format!("({})", items.join(" ")));
+    }
+
+    pub(crate) fn save(&self) {
+        let mut buffer = self.lines.join("\n");
+        buffer.push('\n');
+
+        File::create(&self.path)
+            .unwrap()
+            .write_all(buffer.as_bytes())
+            .unwrap();
+    }
+}
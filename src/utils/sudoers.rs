@@ -0,0 +1,139 @@
+use snafu::{ensure, ResultExt};
+
+use {
+    crate::{
+        errors::{self, ALIResult},
+        utils::command::Command,
+    },
+    std::{
+        fs,
+        io::{self, prelude::*},
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+        process::Stdio,
+    },
+};
+
+const SUDOERS: &str = "/etc/sudoers";
+const SUDOERS_DIR: &str = "/etc/sudoers.d";
+const INCLUDEDIR: &str = "@includedir /etc/sudoers.d";
+
+/// Sorted last, so it takes precedence over the rules of the users.
+const NOPASSWD_DROP_IN: &str = "99-installer-nopasswd";
+
+fn drop_in_path(name: &str) -> PathBuf {
+    let mut path = PathBuf::from(SUDOERS_DIR);
+    path.push(name);
+    path
+}
+
+fn visudo_check(path: &str) -> ALIResult<()> {
+    let status = Command::new("visudo")
+        .args(&["--check", "--quiet", "--file", path])
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap()
+        .wait()
+        .unwrap();
+
+    ensure!(
+        status.success(),
+        errors::SudoersInvalidSnafu {
+            path: PathBuf::from(path)
+        }
+    );
+    Ok(())
+}
+
+/// Makes sure the main file reads the drop-ins, `#includedir` is the legacy
+/// spelling of the same directive.
+fn ensure_includedir() -> ALIResult<()> {
+    let buffer = fs::read_to_string(SUDOERS).context(errors::SudoersSnafu { path: SUDOERS })?;
+
+    let included = buffer.lines().any(|line| {
+        let line = line.trim();
+        line == INCLUDEDIR || line == "#includedir /etc/sudoers.d"
+    });
+
+    if !included {
+        fs::OpenOptions::new()
+            .append(true)
+            .open(SUDOERS)
+            .and_then(|mut file| file.write_all(format!("\n{}\n", INCLUDEDIR).as_bytes()))
+            .context(errors::SudoersSnafu { path: SUDOERS })?;
+        visudo_check(SUDOERS)?;
+    }
+    Ok(())
+}
+
+/// Installs `/etc/sudoers.d/<name>` with the given rules.
+///
+/// The rules are written to a temporary file first, which sudo ignores since
+/// its name contains a dot, and it's moved in place only once visudo accepts
+/// it, so a broken rule can't lock out sudo.
+pub(crate) fn install_drop_in(name: &str, rules: &str) -> ALIResult<()> {
+    ensure_includedir()?;
+    fs::create_dir_all(SUDOERS_DIR).context(errors::SudoersSnafu { path: SUDOERS_DIR })?;
+
+    let path = drop_in_path(name);
+    let tmp_path = drop_in_path(&format!(".{}.tmp", name));
+
+    fs::write(&tmp_path, rules)
+        .and_then(|_| fs::set_permissions(&tmp_path, fs::Permissions::from_mode(0o440)))
+        .context(errors::SudoersSnafu { path: &tmp_path })?;
+
+    if let Err(error) = visudo_check(tmp_path.to_str().unwrap()) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(error);
+    }
+    fs::rename(&tmp_path, &path).context(errors::SudoersSnafu { path })
+}
+
+pub(crate) fn remove_drop_in(name: &str) -> ALIResult<()> {
+    remove_file(drop_in_path(name))
+}
+
+fn remove_file(path: PathBuf) -> ALIResult<()> {
+    match fs::remove_file(&path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => {
+            Err(error).context(errors::SudoersSnafu { path })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Lets the user run sudo without a password while the guard is alive, e.g.
+/// for `makepkg -si`. The rule is removed on drop, also when unwinding.
+pub(crate) struct TemporaryNopasswd {
+    _private: (),
+}
+
+impl TemporaryNopasswd {
+    pub(crate) fn new(user: &str) -> ALIResult<TemporaryNopasswd> {
+        install_drop_in(
+            NOPASSWD_DROP_IN,
+            &format!("{} ALL=(ALL) NOPASSWD: ALL\n", user),
+        )?;
+        Ok(TemporaryNopasswd { _private: () })
+    }
+
+    /// Removes the rule left by an interrupted run.
+    pub(crate) fn remove_stale() -> ALIResult<()> {
+        remove_drop_in(NOPASSWD_DROP_IN)
+    }
+
+    /// Same as `remove_stale` for the system mounted at `root`, from outside
+    /// of it.
+    pub(crate) fn remove_stale_in(root: &Path) -> ALIResult<()> {
+        let path = drop_in_path(NOPASSWD_DROP_IN);
+        remove_file(root.join(path.strip_prefix("/").unwrap()))
+    }
+}
+
+impl Drop for TemporaryNopasswd {
+    fn drop(&mut self) {
+        if let Err(error) = remove_drop_in(NOPASSWD_DROP_IN) {
+            eprintln!("{}", error);
+        }
+    }
+}